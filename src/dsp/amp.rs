@@ -1,58 +1,371 @@
-use crate::dsp::{fast_tanh, flush_denormals};
-use crate::params::{AmpModel, GtrParams};
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
 
-pub struct Amp {
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::filters::{OnePoleHp, OnePoleLp};
+use crate::dsp::flush_denormals;
+use crate::dsp::nam::NeuralModel;
+use crate::dsp::poweramp::{PowerAmp, PowerDef};
+use crate::dsp::shapers::{Adaa1, Shaper};
+use crate::dsp::simd::Sample;
+use crate::dsp::tonestack::{ToneStack, ToneStackType};
+use crate::params::{db_to_gain, AmpModel};
+use crate::values::ParamValues;
+
+const MAX_STAGES: usize = 3;
+/// Most voicing files the User Voicing param can pick from.
+pub const MAX_USER_VOICINGS: usize = 16;
+
+/// One gain stage: coupling-cap high-pass -> gain -> shaper -> Miller-cap low-pass.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct StageDef {
+    /// Gain at drive = 0.
+    gain: f32,
+    /// Extra gain at drive = 1.
+    drive_gain: f32,
+    shaper: Shaper,
+    hp_hz: f32,
+    lp_hz: f32,
+}
+
+/// Complete amp description. The built-in models are defined below; more can
+/// be loaded from JSON files with the same shape, e.g.
+///
+/// ```json
+/// {
+///   "name": "Dirty Plexi",
+///   "stages": [
+///     { "gain": 2.0, "drive_gain": 5.0, "shaper": { "type": "triode" },
+///       "hp_hz": 90.0, "lp_hz": 8000.0 },
+///     { "gain": 1.5, "drive_gain": 2.0, "shaper": { "type": "tube", "bias": 0.1 },
+///       "hp_hz": 40.0, "lp_hz": 6000.0 }
+///   ],
+///   "tone_stack": "marshall",
+///   "tone_stack_after": 2,
+///   "power": { "gain": 1.8, "nfb": 0.2, "presence": 0.5, "resonance": 0.5 },
+///   "level": 0.7
+/// }
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Voicing {
+    pub name: Cow<'static, str>,
+    /// Up to `MAX_STAGES` gain stages, in signal order.
+    stages: Cow<'static, [StageDef]>,
+    tone_stack: ToneStackType,
+    /// Number of preamp stages before the tone stack.
+    tone_stack_after: usize,
+    power: PowerDef,
+    level: f32,
+}
+
+/// Tube into soft germanium: glassy, compresses before it breaks up.
+static CLEAN_GLASS: Voicing = Voicing {
+    name: Cow::Borrowed("Clean Glass"),
+    stages: Cow::Borrowed(&[
+        StageDef {
+            gain: 1.0,
+            drive_gain: 2.5,
+            shaper: Shaper::Tube { bias: 0.08 },
+            hp_hz: 30.0,
+            lp_hz: 12000.0,
+        },
+        StageDef {
+            gain: 1.0,
+            drive_gain: 1.0,
+            shaper: Shaper::GermaniumDiode,
+            hp_hz: 20.0,
+            lp_hz: 10000.0,
+        },
+    ]),
+    tone_stack: ToneStackType::Fender,
+    tone_stack_after: 1,
+    // lots of feedback: big clean headroom
+    power: PowerDef {
+        gain: 1.2,
+        nfb: 0.4,
+        presence: 0.4,
+        resonance: 0.3,
+    },
+    level: 0.9,
+};
+
+/// Tight triode into silicon clipping: trimmed lows, firm crunch.
+static CRUNCH_TIGHT: Voicing = Voicing {
+    name: Cow::Borrowed("Crunch Tight"),
+    stages: Cow::Borrowed(&[
+        StageDef {
+            gain: 2.0,
+            drive_gain: 6.0,
+            shaper: Shaper::Triode,
+            hp_hz: 120.0,
+            lp_hz: 8000.0,
+        },
+        StageDef {
+            gain: 1.5,
+            drive_gain: 2.0,
+            shaper: Shaper::SiliconDiode,
+            hp_hz: 40.0,
+            lp_hz: 6500.0,
+        },
+    ]),
+    tone_stack: ToneStackType::Marshall,
+    tone_stack_after: 2,
+    power: PowerDef {
+        gain: 1.8,
+        nfb: 0.25,
+        presence: 0.5,
+        resonance: 0.5,
+    },
+    level: 0.7,
+};
+
+/// Three cascaded stages ending in a kneed hard clip: dense, focused lead.
+static LEAD_EDM: Voicing = Voicing {
+    name: Cow::Borrowed("Lead EDM"),
+    stages: Cow::Borrowed(&[
+        StageDef {
+            gain: 3.0,
+            drive_gain: 8.0,
+            shaper: Shaper::Tube { bias: 0.15 },
+            hp_hz: 150.0,
+            lp_hz: 9000.0,
+        },
+        StageDef {
+            gain: 2.0,
+            drive_gain: 4.0,
+            shaper: Shaper::Triode,
+            hp_hz: 60.0,
+            lp_hz: 7000.0,
+        },
+        StageDef {
+            gain: 1.5,
+            drive_gain: 2.0,
+            shaper: Shaper::HardClip { knee: 0.4 },
+            hp_hz: 30.0,
+            lp_hz: 5500.0,
+        },
+    ]),
+    tone_stack: ToneStackType::Vox,
+    tone_stack_after: 2,
+    // little feedback: the power section joins in on the distortion
+    power: PowerDef {
+        gain: 2.0,
+        nfb: 0.15,
+        presence: 0.6,
+        resonance: 0.4,
+    },
+    level: 0.55,
+};
+
+impl Voicing {
+    fn validate(&self) -> Result<(), String> {
+        if self.stages.is_empty() || self.stages.len() > MAX_STAGES {
+            return Err(format!("needs 1 to {MAX_STAGES} stages"));
+        }
+        if self.tone_stack_after > self.stages.len() {
+            return Err("tone_stack_after is past the last stage".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Reads every `*.json` voicing in `dir`, sorted by file name, keeping at
+/// most `MAX_USER_VOICINGS`. Files that don't parse or don't fit are logged
+/// and skipped. Blocking; keep it off the audio thread.
+pub fn load_user_voicings(dir: &Path) -> Vec<Voicing> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut voicings = Vec::new();
+    for path in &paths {
+        let parsed = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| serde_json::from_str::<Voicing>(&text).map_err(|e| e.to_string()))
+            .and_then(|v| v.validate().map(|()| v));
+        match parsed {
+            Ok(_) if voicings.len() == MAX_USER_VOICINGS => nih_log!(
+                "Skipping amp voicing {}: only the first {MAX_USER_VOICINGS} can be selected",
+                path.display()
+            ),
+            Ok(v) => voicings.push(v),
+            Err(e) => nih_error!("Skipping amp voicing {}: {e}", path.display()),
+        }
+    }
+    voicings
+}
+
+fn voicing(model: AmpModel, user_slot: usize, user: &[Voicing]) -> &Voicing {
+    match model {
+        AmpModel::CleanGlass => &CLEAN_GLASS,
+        AmpModel::CrunchTight => &CRUNCH_TIGHT,
+        AmpModel::LeadEdm => &LEAD_EDM,
+        // until a model file is loaded
+        AmpModel::Neural => &CLEAN_GLASS,
+        AmpModel::User => user.get(user_slot).unwrap_or(&CLEAN_GLASS),
+    }
+}
+
+struct Stage<S: Sample> {
+    hp: OnePoleHp<S>,
+    shaper: S::Lanes<Adaa1>,
+    lp: OnePoleLp<S>,
+}
+
+pub struct Amp<S: Sample = f32> {
     sr: f32,
+    model: AmpModel,
+    /// Index into `user_voicings` used by `AmpModel::User`.
+    user_slot: usize,
+    user_voicings: Arc<[Voicing]>,
+    stages: [Stage<S>; MAX_STAGES],
+    tone_stack: ToneStack<S>,
+    power: PowerAmp<S>,
+    dc_block: OnePoleHp<S>,
+    /// Loaded NAM model per lane, swapped in from the background loader.
+    pub neural: S::Lanes<Option<Box<NeuralModel>>>,
+    nam_in: f32,
+    nam_out: f32,
 }
 
-impl Amp {
+impl<S: Sample> Amp<S> {
     pub fn new(sr: f32) -> Self {
-        Self { sr }
+        let mut a = Self {
+            sr,
+            model: AmpModel::CrunchTight,
+            user_slot: 0,
+            user_voicings: Arc::from([]),
+            stages: core::array::from_fn(|_| Stage {
+                hp: OnePoleHp::new(),
+                shaper: S::lanes(|| Adaa1::new(Shaper::Soft)),
+                lp: OnePoleLp::new(),
+            }),
+            tone_stack: ToneStack::new(),
+            power: PowerAmp::new(sr),
+            dc_block: OnePoleHp::new(),
+            neural: S::lanes(|| None),
+            nam_in: 1.0,
+            nam_out: 1.0,
+        };
+        a.reset(sr);
+        a
     }
 
     pub fn reset(&mut self, sr: f32) {
         self.sr = sr;
+        for st in self.stages.iter_mut() {
+            st.hp = OnePoleHp::new();
+            st.shaper.as_mut().iter_mut().for_each(Adaa1::reset);
+            st.lp = OnePoleLp::new();
+        }
+        self.tone_stack.reset();
+        self.power.reset(sr);
+        self.dc_block = OnePoleHp::new();
+        self.dc_block.set_cutoff(self.sr, 10.0);
+        for nn in self.neural.as_mut().iter_mut().flatten() {
+            nn.reset();
+        }
+        self.set_filters();
     }
 
-    #[inline]
-    pub fn process_sample(&mut self, x: f32, p: &GtrParams) -> f32 {
-        let drive = p.drive.value().clamp(0.0, 1.0);
-        let model = p.amp_model.value();
-
-        // Map drive + model to pre-gain and mix amounts
-        let (pre_gain, sat_strength, post_atten) = match model {
-            AmpModel::CleanGlass => {
-                // Mostly clean, some gentle color
-                let pre = 1.0 + 3.0 * drive; // up to ~4x
-                let sat = 0.7 + 0.3 * drive; // slightly more drive at max
-                let post = 0.9;
-                (pre, sat, post)
-            }
-            AmpModel::CrunchTight => {
-                // Rock/modern crunch
-                let pre = 1.5 + 6.0 * drive; // up to ~7.5x
-                let sat = 0.9 + 0.5 * drive;
-                let post = 0.7;
-                (pre, sat, post)
+    /// Voicings loaded from disk, selectable through `AmpModel::User`.
+    pub fn set_user_voicings(&mut self, voicings: Arc<[Voicing]>) {
+        self.user_voicings = voicings;
+        self.set_filters();
+    }
+
+    /// Called once per processing block. Picks up model and tone stack changes.
+    pub fn update_params(&mut self, p: &ParamValues, model: AmpModel) {
+        let user_slot = (p.user_voicing - 1).max(0) as usize;
+        if model != self.model || user_slot != self.user_slot {
+            self.model = model;
+            self.user_slot = user_slot;
+            self.set_filters();
+        }
+
+        self.tone_stack.set(
+            self.sr,
+            voicing(self.model, self.user_slot, &self.user_voicings).tone_stack,
+            p.bass,
+            p.mid,
+            p.treble,
+        );
+
+        self.nam_in = db_to_gain(p.nam_input_db);
+        self.nam_out = db_to_gain(p.nam_output_db);
+    }
+
+    /// Processing delay in samples: half a sample per ADAA shaper.
+    pub fn latency(&self) -> f32 {
+        if self.model == AmpModel::Neural && self.neural_loaded() {
+            return 0.0;
+        }
+        let stages = voicing(self.model, self.user_slot, &self.user_voicings)
+            .stages
+            .len();
+        // plus the power amp shaper
+        0.5 * (stages + 1) as f32
+    }
+
+    /// The loader swaps models into all lanes at once.
+    fn neural_loaded(&self) -> bool {
+        self.neural.as_ref().iter().all(Option::is_some)
+    }
+
+    fn set_filters(&mut self) {
+        for (def, st) in voicing(self.model, self.user_slot, &self.user_voicings)
+            .stages
+            .iter()
+            .zip(self.stages.iter_mut())
+        {
+            st.hp.set_cutoff(self.sr, def.hp_hz);
+            st.lp.set_cutoff(self.sr, def.lp_hz);
+            for shaper in st.shaper.as_mut() {
+                shaper.set_shaper(def.shaper);
             }
-            AmpModel::LeadEdm => {
-                // Focused high-gain
-                let pre = 2.0 + 10.0 * drive; // up to ~12x
-                let sat = 1.1 + 0.6 * drive;
-                let post = 0.6;
-                (pre, sat, post)
+        }
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, x: S, p: &ParamValues) -> S {
+        if self.model == AmpModel::Neural && self.neural_loaded() {
+            let (nam_in, nam_out) = (self.nam_in, self.nam_out);
+            return x.map_lanes(&mut self.neural, |nn, x| match nn {
+                Some(nn) => flush_denormals(nn.process(x * nam_in) * nn.normalize * nam_out),
+                None => x,
+            });
+        }
+
+        let drive = p.drive.clamp(0.0, 1.0);
+        let v = voicing(self.model, self.user_slot, &self.user_voicings);
+
+        let mut y = x;
+        for (i, (def, st)) in v.stages.iter().zip(self.stages.iter_mut()).enumerate() {
+            y = st.hp.process(y);
+            // ADAA instead of oversampling keeps aliasing down at 1x
+            y = (y * (def.gain + def.drive_gain * drive)).map_lanes(&mut st.shaper, Adaa1::process);
+            y = st.lp.process(y);
+
+            // Passive TMB stack between gain stages
+            if i + 1 == v.tone_stack_after {
+                y = self.tone_stack.process(y);
             }
-        };
+        }
 
-        // Pre-boost into waveshaper
-        let pre = x * pre_gain;
-        let shaped = fast_tanh(pre * sat_strength);
+        // Power amp with supply sag
+        y = self.power.process(y, p.sag, &v.power);
 
-        // Wet/dry mix based on drive
-        let wet_mix = 0.2 + 0.7 * drive; // 0.2..0.9
-        let dry_mix = 1.0 - wet_mix;
-        let y = pre * dry_mix + shaped * wet_mix;
+        // Biased stages leave DC behind
+        y = self.dc_block.process(y);
 
-        flush_denormals(y * post_atten)
+        (y * v.level).flush_denormals()
     }
 }
@@ -1,15 +1,16 @@
 use crate::dsp::filters::{OnePoleHp, TiltEq};
-use crate::dsp::flush_denormals;
-use crate::params::{CabModel, GtrParams};
+use crate::dsp::simd::Sample;
+use crate::params::CabModel;
+use crate::values::ParamValues;
 
-pub struct Cab {
+pub struct Cab<S: Sample = f32> {
     sr: f32,
-    tilt: TiltEq,
-    presence_hp: OnePoleHp,
-    air_hp: OnePoleHp,
+    tilt: TiltEq<S>,
+    presence_hp: OnePoleHp<S>,
+    air_hp: OnePoleHp<S>,
 }
 
-impl Cab {
+impl<S: Sample> Cab<S> {
     pub fn new(sr: f32) -> Self {
         let mut c = Self {
             sr,
@@ -40,13 +41,12 @@ impl Cab {
     }
 
     #[inline]
-    pub fn process_sample(&mut self, x: f32, p: &GtrParams) -> f32 {
+    pub fn process_sample(&mut self, x: S, cab: CabModel, p: &ParamValues) -> S {
         let mut y = x;
 
-        let tone = p.tone.value(); // 0..1
-        let pres = p.presence.value(); // 0..1
-        let air = p.air.value(); // 0..1
-        let cab = p.cab_model.value();
+        let tone = p.tone; // 0..1
+        let pres = p.presence; // 0..1
+        let air = p.air; // 0..1
 
         // Cab-dependent tone bias
         let tone_bias = match cab {
@@ -78,6 +78,6 @@ impl Cab {
             y += hi_hi * (base - 1.0);
         }
 
-        flush_denormals(y)
+        y.flush_denormals()
     }
 }
@@ -1,26 +1,57 @@
 // src/dsp/chain.rs
-use crate::dsp::amp::Amp;
+use std::sync::Arc;
+
+use crate::dsp::amp::{Amp, Voicing};
 use crate::dsp::cab::Cab;
+use crate::dsp::crusher::Crusher;
 use crate::dsp::filters::OnePoleHp;
 use crate::dsp::gate::Gate;
-use crate::params::GtrParams;
+use crate::dsp::nam::NeuralModel;
+use crate::dsp::simd::Sample;
+use crate::dsp::slots::SlotOrder;
+use crate::dsp::transient::TransientShaper;
+use crate::params::{
+    AmpModel, CabModel, ChainModule, CrushPlacement, TransientPlacement, CHAIN_SLOTS,
+    DEFAULT_CHAIN_ORDER,
+};
+use crate::values::{ParamValues, MORPH_MODELS};
 
-pub struct GuitarChain {
+/// Guitar chain for one channel (`f32`), or for several channels at once in
+/// SIMD lanes (`F32x2`).
+pub struct GuitarChain<S: Sample = f32> {
     sr: f32,
-    gate: Gate,
-    pre_lowcut: OnePoleHp,
-    amp: Amp,
-    cab: Cab,
+    gate: Gate<S>,
+    pre_lowcut: OnePoleHp<S>,
+    /// One amp and cab per model heard while morphing between models.
+    amps: [Amp<S>; MORPH_MODELS],
+    cabs: [Cab<S>; MORPH_MODELS],
+    /// Model each instance plays, `None` while it's free.
+    amp_models: [Option<AmpModel>; MORPH_MODELS],
+    cab_models: [Option<CabModel>; MORPH_MODELS],
+    /// Instance playing the loudest amp model, for latency.
+    main_amp: usize,
+    /// Instance holding the NAM models.
+    neural_at: usize,
+    crusher: Crusher<S>,
+    transient: TransientShaper<S>,
+    order: SlotOrder<ChainModule, CHAIN_SLOTS>,
 }
 
-impl GuitarChain {
+impl<S: Sample> GuitarChain<S> {
     pub fn new(sr: f32) -> Self {
         let mut chain = Self {
             sr,
             gate: Gate::new(sr),
             pre_lowcut: OnePoleHp::new(),
-            amp: Amp::new(sr),
-            cab: Cab::new(sr),
+            amps: core::array::from_fn(|_| Amp::new(sr)),
+            cabs: core::array::from_fn(|_| Cab::new(sr)),
+            amp_models: [None; MORPH_MODELS],
+            cab_models: [None; MORPH_MODELS],
+            main_amp: 0,
+            neural_at: 0,
+            crusher: Crusher::new(sr, &[0x1234_5678, 0x8765_4321]),
+            transient: TransientShaper::new(sr),
+            order: SlotOrder::new(sr, DEFAULT_CHAIN_ORDER),
         };
         chain.reset(sr);
         chain
@@ -31,35 +62,305 @@ impl GuitarChain {
         self.gate.reset(sr);
         self.pre_lowcut = OnePoleHp::new();
         self.pre_lowcut.set_cutoff(self.sr, 100.0);
-        self.amp.reset(sr);
-        self.cab.reset(sr);
+        self.amps.iter_mut().for_each(|amp| amp.reset(sr));
+        self.cabs.iter_mut().for_each(|cab| cab.reset(sr));
+        self.crusher.reset(sr);
+        self.transient.reset(sr);
+        self.order.reset(sr);
+    }
+
+    pub fn set_user_voicings(&mut self, voicings: Arc<[Voicing]>) {
+        for amp in self.amps.iter_mut() {
+            amp.set_user_voicings(voicings.clone());
+        }
+    }
+
+    /// Processing delay in samples, for latency-compensating the dry signal.
+    pub fn latency(&self) -> f32 {
+        self.amps[self.main_amp].latency()
+    }
+
+    /// Slots, one per lane, for the NAM model used by `AmpModel::Neural`.
+    pub fn neural_models_mut(&mut self) -> &mut S::Lanes<Option<Box<NeuralModel>>> {
+        &mut self.amps[self.neural_at].neural
     }
 
     /// Called once per processing block to update filter coefficients etc.
-    pub fn update_params(&mut self, p: &GtrParams) {
+    pub fn update_params(&mut self, p: &ParamValues) {
         self.gate.update_params(self.sr, p);
         // user-controlled low cut
-        self.pre_lowcut.set_cutoff(self.sr, p.low_cut_hz.value());
-        self.cab.update_params(self.sr);
+        self.pre_lowcut.set_cutoff(self.sr, p.low_cut_hz);
+
+        // Models keep their instance for as long as they're heard, so their
+        // state carries on. New ones start from a reset instance.
+        let (main_amp, fresh) = assign(&mut self.amp_models, &p.model_morph.amps);
+        self.main_amp = main_amp;
+        if let Some(i) = self
+            .amp_models
+            .iter()
+            .position(|&m| m == Some(AmpModel::Neural))
+        {
+            self.move_neural(i);
+        }
+        for ((amp, model), fresh) in self.amps.iter_mut().zip(self.amp_models).zip(fresh) {
+            if let Some(model) = model {
+                if fresh {
+                    amp.reset(self.sr);
+                }
+                amp.update_params(p, model);
+            }
+        }
+
+        let (_, fresh) = assign(&mut self.cab_models, &p.model_morph.cabs);
+        for (cab, fresh) in self.cabs.iter_mut().zip(fresh) {
+            if fresh {
+                cab.reset(self.sr);
+            }
+            cab.update_params(self.sr);
+        }
+        self.crusher.update_params(self.sr, p);
+        self.order.set_target(p.chain_order());
     }
 
     #[inline]
-    pub fn process_sample(&mut self, x: f32, p: &GtrParams) -> f32 {
+    pub fn process_sample(&mut self, x: S, p: &ParamValues) -> S {
         // Input gain before anything
         let mut s = x * p.input_linear();
 
-        // Noise gate
-        s = self.gate.process_sample(s);
+        let transient_at = p.transient_placement;
+        let crush_at = p.crush_placement;
+
+        let (order, gain) = self.order.next();
+        for module in order {
+            match module {
+                // Noise gate
+                ChainModule::Gate => s = self.gate.process_sample(s),
+                // Low cut, normally in front of the amp
+                ChainModule::LowCut => s = self.pre_lowcut.process(s),
+                ChainModule::Amp => {
+                    if transient_at == TransientPlacement::PreAmp {
+                        s = self.transient.process_sample(s, p);
+                    }
+                    if crush_at == CrushPlacement::PreAmp {
+                        s = self.crusher.process_sample(s, p);
+                    }
+
+                    // Amp / distortion, every model heard at its share
+                    let mut out = S::splat(0.0);
+                    for (amp, model) in self.amps.iter_mut().zip(self.amp_models) {
+                        if let Some(model) = model {
+                            out += amp.process_sample(s, p) * p.model_morph.amp_share(model);
+                        }
+                    }
+                    s = out;
+                }
+                ChainModule::Cab => {
+                    // Cab voicing + tone, presence, air
+                    let mut out = S::splat(0.0);
+                    for (cab, model) in self.cabs.iter_mut().zip(self.cab_models) {
+                        if let Some(model) = model {
+                            out += cab.process_sample(s, model, p) * p.model_morph.cab_share(model);
+                        }
+                    }
+                    s = out;
+
+                    if transient_at == TransientPlacement::PostCab {
+                        s = self.transient.process_sample(s, p);
+                    }
+                    if crush_at == CrushPlacement::PostCab {
+                        s = self.crusher.process_sample(s, p);
+                    }
+                }
+            }
+        }
+
+        // Dips briefly while the slot order changes
+        s * gain
+    }
+
+    /// Hands the NAM models to amp instance `i`.
+    fn move_neural(&mut self, i: usize) {
+        if i != self.neural_at {
+            let models =
+                std::mem::replace(&mut self.amps[self.neural_at].neural, S::lanes(|| None));
+            self.amps[i].neural = models;
+            self.neural_at = i;
+        }
+    }
+}
+
+/// Gives every model heard in `shares` an instance: the one already playing
+/// it, else a free one. Instances whose model has faded out are freed first.
+/// Returns the instance of the loudest model and which instances were just
+/// assigned, so they can be reset.
+fn assign<M: Copy + PartialEq>(
+    playing: &mut [Option<M>; MORPH_MODELS],
+    shares: &[(M, f32); MORPH_MODELS],
+) -> (usize, [bool; MORPH_MODELS]) {
+    let heard = |m: M| shares.iter().any(|&(s, w)| s == m && w > 0.0);
+    for slot in playing.iter_mut() {
+        if slot.is_some_and(|m| !heard(m)) {
+            *slot = None;
+        }
+    }
+
+    let mut fresh = [false; MORPH_MODELS];
+    let (mut main, mut loudest) = (0, 0.0);
+    for &(model, share) in shares.iter().filter(|(_, w)| *w > 0.0) {
+        // each model is listed once, so there's always a free instance
+        let i = match playing.iter().position(|&m| m == Some(model)) {
+            Some(i) => i,
+            None => {
+                let i = playing.iter().position(Option::is_none).unwrap_or(0);
+                playing[i] = Some(model);
+                fresh[i] = true;
+                i
+            }
+        };
+        if share > loudest {
+            (main, loudest) = (i, share);
+        }
+    }
+    (main, fresh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::simd::F32x2;
+    use crate::params::GtrParams;
+    use nih_plug::prelude::Enum;
+
+    #[test]
+    fn models_keep_their_instance_while_heard() {
+        use AmpModel::*;
+        let mut playing = [None; MORPH_MODELS];
+
+        let (main, fresh) = assign(
+            &mut playing,
+            &[
+                (CleanGlass, 0.7),
+                (LeadEdm, 0.3),
+                (LeadEdm, 0.0),
+                (LeadEdm, 0.0),
+            ],
+        );
+        assert_eq!(main, 0);
+        assert_eq!(fresh, [true, true, false, false]);
+
+        // a third and fourth model come in while the first two still play
+        let shares = [
+            (CleanGlass, 0.4),
+            (CrunchTight, 0.2),
+            (LeadEdm, 0.3),
+            (Neural, 0.1),
+        ];
+        let (main, fresh) = assign(&mut playing, &shares);
+        assert_eq!(main, 0);
+        assert_eq!(fresh, [false, false, true, true]);
+        assert!(
+            playing
+                == [
+                    Some(CleanGlass),
+                    Some(LeadEdm),
+                    Some(CrunchTight),
+                    Some(Neural)
+                ]
+        );
+
+        // a faded-out model frees its instance, and a new one gets it reset
+        let shares = [
+            (User, 0.5),
+            (CrunchTight, 0.0),
+            (LeadEdm, 0.5),
+            (Neural, 0.0),
+        ];
+        let (main, fresh) = assign(&mut playing, &shares);
+        assert_eq!(main, 0);
+        assert_eq!(fresh, [true, false, false, false]);
+        assert!(playing == [Some(User), Some(LeadEdm), None, None]);
+    }
+
+    #[test]
+    fn stereo_lanes_match_two_mono_chains() {
+        let sr = 48000.0;
+        // Crush stays off: each lane's crusher has its own noise seed
+        let mut v = ParamValues::from_params(&GtrParams::default());
+        v.drive = 0.8;
+        for index in 0..AmpModel::variants().len() {
+            let model = AmpModel::from_index(index);
+            v.amp_model = model;
+            v.model_morph.play_amp(model);
+
+            let mut left = GuitarChain::<f32>::new(sr);
+            let mut right = GuitarChain::<f32>::new(sr);
+            let mut stereo = GuitarChain::<F32x2>::new(sr);
+            left.update_params(&v);
+            right.update_params(&v);
+            stereo.update_params(&v);
+
+            for i in 0..4096 {
+                let t = i as f32 / sr;
+                let l = 0.6 * (std::f32::consts::TAU * 110.0 * t).sin();
+                let r = 0.3 * (std::f32::consts::TAU * 165.0 * t).sin();
+                let F32x2(out) = stereo.process_sample(F32x2::new(l, r), &v);
+                let mono = [left.process_sample(l, &v), right.process_sample(r, &v)];
+                let name = AmpModel::variants()[index];
+                assert!(out == mono, "{name} at {i}: {out:?} vs {mono:?}");
+            }
+        }
+    }
+
+    /// Timing only, run it with
+    /// `cargo test --release stereo_lanes_speed -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn stereo_lanes_speed() {
+        use std::hint::black_box;
+        use std::time::Instant;
 
-        // Pre-amp low cut
-        s = self.pre_lowcut.process(s);
+        let sr = 48000.0;
+        let len = 10 * sr as usize;
+        let mut v = ParamValues::from_params(&GtrParams::default());
+        v.drive = 0.8;
+        for index in 0..AmpModel::variants().len() {
+            let model = AmpModel::from_index(index);
+            v.amp_model = model;
+            v.model_morph.play_amp(model);
+            let input = |i: usize| {
+                (
+                    0.5 * (i as f32 * 0.0123).sin(),
+                    0.4 * (i as f32 * 0.0171).sin(),
+                )
+            };
 
-        // Amp / distortion
-        s = self.amp.process_sample(s, p);
+            let mut stereo = GuitarChain::<F32x2>::new(sr);
+            stereo.update_params(&v);
+            let start = Instant::now();
+            for i in 0..len {
+                let (l, r) = input(i);
+                black_box(stereo.process_sample(F32x2::new(l, r), &v));
+            }
+            let simd = start.elapsed();
 
-        // Cab voicing + tone, presence, air
-        s = self.cab.process_sample(s, p);
+            let mut left = GuitarChain::<f32>::new(sr);
+            let mut right = GuitarChain::<f32>::new(sr);
+            left.update_params(&v);
+            right.update_params(&v);
+            let start = Instant::now();
+            for i in 0..len {
+                let (l, r) = input(i);
+                black_box((left.process_sample(l, &v), right.process_sample(r, &v)));
+            }
+            let scalar = start.elapsed();
 
-        s
+            println!(
+                "{:<14} F32x2 {:6.1} ns/frame, 2x f32 {:6.1} ns/frame, {:.2}x",
+                AmpModel::variants()[index],
+                simd.as_nanos() as f64 / len as f64,
+                scalar.as_nanos() as f64 / len as f64,
+                scalar.as_secs_f64() / simd.as_secs_f64()
+            );
+        }
     }
 }
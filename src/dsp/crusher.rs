@@ -0,0 +1,127 @@
+use crate::dsp::filters::OnePoleLp;
+use crate::dsp::rng::XorShift32;
+use crate::dsp::simd::Sample;
+use crate::params::CrushFilter;
+use crate::values::ParamValues;
+
+/// Two cascaded one-poles, enough to take the edge off the stair steps.
+struct Smoothing<S: Sample> {
+    a: OnePoleLp<S>,
+    b: OnePoleLp<S>,
+}
+
+impl<S: Sample> Smoothing<S> {
+    fn new() -> Self {
+        Self {
+            a: OnePoleLp::new(),
+            b: OnePoleLp::new(),
+        }
+    }
+
+    fn set_cutoff(&mut self, sr: f32, hz: f32) {
+        self.a.set_cutoff(sr, hz);
+        self.b.set_cutoff(sr, hz);
+    }
+
+    #[inline]
+    fn process(&mut self, x: S) -> S {
+        self.b.process(self.a.process(x))
+    }
+}
+
+/// Sample & hold state of one lane. Each lane has its own jitter sequence.
+struct Latch {
+    rng: XorShift32,
+    phase: f32,
+    held: f32,
+}
+
+impl Latch {
+    fn reset(&mut self) {
+        self.phase = 1.0; // latch on the first sample
+        self.held = 0.0;
+    }
+}
+
+/// Bit depth + sample-rate reducer. Bit depth may be fractional, jitter
+/// randomizes the hold length. Optional low-pass filters before (anti-alias)
+/// and after (de-stepping) the sample & hold.
+pub struct Crusher<S: Sample = f32> {
+    sr: f32,
+    pre: Smoothing<S>,
+    post: Smoothing<S>,
+    latch: S::Lanes<Latch>,
+}
+
+impl<S: Sample> Crusher<S> {
+    /// Lane `i` seeds its jitter with `seeds[i % seeds.len()]`.
+    pub fn new(sr: f32, seeds: &[u32]) -> Self {
+        let mut seeds = seeds.iter().cycle();
+        let mut c = Self {
+            sr,
+            pre: Smoothing::new(),
+            post: Smoothing::new(),
+            latch: S::lanes(|| Latch {
+                rng: XorShift32::new(*seeds.next().unwrap()),
+                phase: 0.0,
+                held: 0.0,
+            }),
+        };
+        c.reset(sr);
+        c
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        self.pre = Smoothing::new();
+        self.post = Smoothing::new();
+        self.latch.as_mut().iter_mut().for_each(Latch::reset);
+    }
+
+    /// Called once per processing block.
+    pub fn update_params(&mut self, sr: f32, p: &ParamValues) {
+        self.sr = sr;
+        let nyquist = 0.5 * p.crush_rate_hz;
+        self.pre.set_cutoff(self.sr, nyquist);
+        self.post.set_cutoff(self.sr, nyquist);
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, x: S, p: &ParamValues) -> S {
+        let mix = p.crush_mix;
+        if mix <= 0.0 {
+            return x;
+        }
+
+        let filter = p.crush_filter;
+        let mut y = x;
+
+        if matches!(filter, CrushFilter::Pre | CrushFilter::Both) {
+            y = self.pre.process(y);
+        }
+
+        // Sample & hold at the reduced rate
+        let step = p.crush_rate_hz / self.sr;
+        y = y.map_lanes(&mut self.latch, |l, y| {
+            l.phase += step;
+            if l.phase >= 1.0 {
+                l.phase -= 1.0;
+                // jitter pushes the next latch randomly earlier or later
+                l.phase += 0.5 * p.crush_jitter * l.rng.next_bipolar();
+                l.phase = l.phase.clamp(-1.0, 1.0);
+                l.held = y;
+            }
+            l.held
+        });
+
+        // Quantize; fractional bit depths give in-between step sizes
+        let steps = (p.crush_bits - 1.0).exp2();
+        y = y.map(|y| (y * steps).round() / steps);
+
+        if matches!(filter, CrushFilter::Post | CrushFilter::Both) {
+            y = self.post.process(y);
+        }
+
+        (x + (y - x) * mix).flush_denormals()
+    }
+}
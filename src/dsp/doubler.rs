@@ -0,0 +1,116 @@
+use crate::dsp::filters::{OnePoleLp, TiltEq};
+use crate::dsp::rng::XorShift32;
+use crate::dsp::{flush_denormals, time_to_coeff};
+use crate::values::ParamValues;
+
+/// Longest delay the second take can trail by, including drift.
+const MAX_DELAY_S: f32 = 0.040;
+/// Peak delay wobble at full drift, in seconds. The 2 Hz wander turns this
+/// into roughly ±20 cents of pitch drift on a typical step, ±60 at worst.
+const MAX_DRIFT_S: f32 = 0.0015;
+
+/// Fakes a second, hard-panned take from a mono DI.
+///
+/// The double is a delayed copy of the mono sum whose delay time wanders
+/// slowly at random (micro pitch drift) and which runs through a slightly
+/// darker EQ, so the two sides decorrelate like separate performances.
+pub struct Doubler {
+    sr: f32,
+    buf: Vec<f32>,
+    idx: usize,
+    rng: XorShift32,
+    drift_target: f32,
+    drift: OnePoleLp,
+    drift_count: u32,
+    time_ms: f32,
+    time_coeff: f32,
+    tilt: TiltEq,
+}
+
+impl Doubler {
+    pub fn new(sr: f32) -> Self {
+        let mut d = Self {
+            sr,
+            buf: Vec::new(),
+            idx: 0,
+            rng: XorShift32::new(0x0D0B_1E55),
+            drift_target: 0.0,
+            drift: OnePoleLp::new(),
+            drift_count: 0,
+            time_ms: -1.0,
+            time_coeff: 0.0,
+            tilt: TiltEq::new(),
+        };
+        d.initialize(sr);
+        d
+    }
+
+    /// Allocates the delay buffer. Must not be called from the audio thread.
+    pub fn initialize(&mut self, sr: f32) {
+        self.buf = vec![0.0; ((MAX_DELAY_S * sr) as usize).max(4)];
+        self.reset(sr);
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        self.buf.fill(0.0);
+        self.idx = 0;
+        self.drift_target = 0.0;
+        self.drift = OnePoleLp::new();
+        self.drift.set_cutoff(sr, 2.0); // slow wander
+        self.drift_count = 0;
+        self.time_ms = -1.0; // snaps to the parameter on the next sample
+        self.time_coeff = time_to_coeff(0.05, sr);
+        self.tilt = TiltEq::new();
+        self.tilt.set_pivot(sr, 1200.0);
+    }
+
+    #[inline]
+    pub fn process(&mut self, l: f32, r: f32, p: &ParamValues) -> (f32, f32) {
+        let amount = p.double_amount;
+        let mono = 0.5 * (l + r);
+
+        let len = self.buf.len();
+        self.idx += 1;
+        if self.idx >= len {
+            self.idx = 0;
+        }
+        self.buf[self.idx] = mono;
+
+        if amount <= 0.0 {
+            return (l, r);
+        }
+
+        // New random drift target every ~200 ms
+        if self.drift_count == 0 {
+            self.drift_target = self.rng.next_bipolar();
+            self.drift_count = (0.2 * self.sr) as u32;
+        }
+        self.drift_count -= 1;
+        let drift = self.drift.process(self.drift_target);
+
+        // Smooth time changes so automating it doesn't zipper
+        let target_ms = p.double_time_ms;
+        if self.time_ms < 0.0 {
+            self.time_ms = target_ms;
+        }
+        self.time_ms = target_ms + (self.time_ms - target_ms) * self.time_coeff;
+
+        let delay_s = self.time_ms * 0.001 + drift * MAX_DRIFT_S * p.double_drift;
+        let delay = (delay_s * self.sr).clamp(1.0, (len - 2) as f32);
+
+        let pos = self.idx as f32 + len as f32 - delay;
+        let i0 = pos.floor();
+        let frac = pos - i0;
+        let i0 = i0 as usize % len;
+        let i1 = (i0 + 1) % len;
+        let take = self.buf[i0] + (self.buf[i1] - self.buf[i0]) * frac;
+
+        // Slightly darker "second guitar"
+        let take = self.tilt.process(take, -0.3);
+
+        // Left keeps the original, right becomes the second take
+        let r = r + (take - r) * amount;
+        (l, flush_denormals(r))
+    }
+}
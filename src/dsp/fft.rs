@@ -0,0 +1,210 @@
+use core::f32::consts::PI;
+use core::ops::{Add, Mul, Sub};
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+
+    #[inline]
+    pub fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    #[inline]
+    pub fn from_polar(mag: f32, phase: f32) -> Self {
+        let (s, c) = phase.sin_cos();
+        Self::new(mag * c, mag * s)
+    }
+
+    #[inline]
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    #[inline]
+    pub fn scale(self, k: f32) -> Self {
+        Self::new(self.re * k, self.im * k)
+    }
+
+    #[inline]
+    pub fn norm(self) -> f32 {
+        (self.re * self.re + self.im * self.im).sqrt()
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    #[inline]
+    fn add(self, o: Complex) -> Complex {
+        Complex::new(self.re + o.re, self.im + o.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    #[inline]
+    fn sub(self, o: Complex) -> Complex {
+        Complex::new(self.re - o.re, self.im - o.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    #[inline]
+    fn mul(self, o: Complex) -> Complex {
+        Complex::new(
+            self.re * o.re - self.im * o.im,
+            self.re * o.im + self.im * o.re,
+        )
+    }
+}
+
+/// In-place iterative radix-2 complex FFT. Tables are built in `new`, so
+/// `process` never allocates.
+pub struct ComplexFft {
+    n: usize,
+    twiddles: Vec<Complex>,
+    rev: Vec<usize>,
+}
+
+impl ComplexFft {
+    /// `n` must be a power of two.
+    pub fn new(n: usize) -> Self {
+        assert!(n.is_power_of_two(), "FFT size must be a power of two");
+        let bits = n.trailing_zeros();
+        let twiddles = (0..n / 2)
+            .map(|k| Complex::from_polar(1.0, -2.0 * PI * k as f32 / n as f32))
+            .collect();
+        let rev = (0..n)
+            .map(|i| {
+                if bits == 0 {
+                    0
+                } else {
+                    i.reverse_bits() >> (usize::BITS - bits)
+                }
+            })
+            .collect();
+        Self { n, twiddles, rev }
+    }
+
+    /// Unnormalized forward (or inverse, with conjugated twiddles) transform.
+    pub fn process(&self, buf: &mut [Complex], inverse: bool) {
+        let n = self.n;
+        debug_assert_eq!(buf.len(), n);
+
+        for i in 0..n {
+            let j = self.rev[i];
+            if j > i {
+                buf.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+        while len <= n {
+            let half = len / 2;
+            let step = n / len;
+            for start in (0..n).step_by(len) {
+                for k in 0..half {
+                    let mut w = self.twiddles[k * step];
+                    if inverse {
+                        w = w.conj();
+                    }
+                    let a = buf[start + k];
+                    let b = buf[start + k + half] * w;
+                    buf[start + k] = a + b;
+                    buf[start + k + half] = a - b;
+                }
+            }
+            len <<= 1;
+        }
+    }
+}
+
+/// Real-input FFT of size `n`, computed with one complex FFT of size `n / 2`
+/// plus a split step. Spectra have `n / 2 + 1` bins.
+pub struct RealFft {
+    n: usize,
+    half: ComplexFft,
+    twiddles: Vec<Complex>,
+}
+
+impl RealFft {
+    /// `n` must be a power of two, at least 2.
+    pub fn new(n: usize) -> Self {
+        assert!(n >= 2, "real FFT size must be at least 2");
+        let twiddles = (0..=n / 2)
+            .map(|k| Complex::from_polar(1.0, -2.0 * PI * k as f32 / n as f32))
+            .collect();
+        Self {
+            n,
+            half: ComplexFft::new(n / 2),
+            twiddles,
+        }
+    }
+
+    /// `input.len() == n`, `output.len() == n / 2 + 1`, `scratch.len() == n / 2`.
+    pub fn forward(&self, input: &[f32], output: &mut [Complex], scratch: &mut [Complex]) {
+        let m = self.n / 2;
+        for k in 0..m {
+            scratch[k] = Complex::new(input[2 * k], input[2 * k + 1]);
+        }
+        self.half.process(scratch, false);
+
+        for k in 0..=m {
+            let zk = scratch[k % m];
+            let zc = scratch[(m - k) % m].conj();
+            let even = (zk + zc).scale(0.5);
+            let odd = (zk - zc) * Complex::new(0.0, -0.5);
+            output[k] = even + self.twiddles[k] * odd;
+        }
+    }
+
+    /// Inverse of `forward`, including the `1 / n` normalization.
+    pub fn inverse(&self, input: &[Complex], output: &mut [f32], scratch: &mut [Complex]) {
+        let m = self.n / 2;
+        for k in 0..m {
+            let xk = input[k];
+            let xc = input[m - k].conj();
+            let even = (xk + xc).scale(0.5);
+            let odd = ((xk - xc) * self.twiddles[k].conj()).scale(0.5);
+            scratch[k] = even + Complex::new(0.0, 1.0) * odd;
+        }
+        self.half.process(scratch, true);
+
+        let norm = 1.0 / m as f32;
+        for k in 0..m {
+            output[2 * k] = scratch[k].re * norm;
+            output[2 * k + 1] = scratch[k].im * norm;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn real_fft_round_trips() {
+        for n in [2, 4, 16, 256, 2048] {
+            let fft = RealFft::new(n);
+            let x: Vec<f32> = (0..n)
+                .map(|i| (i as f32 * 0.37).sin() + 0.25 * (i as f32 * 1.9).cos() - 0.1)
+                .collect();
+            let mut spectrum = vec![Complex::ZERO; n / 2 + 1];
+            let mut scratch = vec![Complex::ZERO; n / 2];
+            let mut y = vec![0.0; n];
+
+            fft.forward(&x, &mut spectrum, &mut scratch);
+            fft.inverse(&spectrum, &mut y, &mut scratch);
+
+            for (a, b) in x.iter().zip(&y) {
+                assert!((a - b).abs() < 1e-4, "n = {n}: {a} != {b}");
+            }
+        }
+    }
+}
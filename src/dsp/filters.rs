@@ -1,15 +1,18 @@
-use crate::dsp::flush_denormals;
+use crate::dsp::simd::Sample;
 use core::f32::consts::PI;
 
 /// One-pole low-pass filter.
-pub struct OnePoleLp {
+pub struct OnePoleLp<S: Sample = f32> {
     a: f32,
-    z: f32,
+    z: S,
 }
 
-impl OnePoleLp {
+impl<S: Sample> OnePoleLp<S> {
     pub fn new() -> Self {
-        Self { a: 0.0, z: 0.0 }
+        Self {
+            a: 0.0,
+            z: S::splat(0.0),
+        }
     }
 
     pub fn set_cutoff(&mut self, sr: f32, hz: f32) {
@@ -19,18 +22,18 @@ impl OnePoleLp {
     }
 
     #[inline]
-    pub fn process(&mut self, x: f32) -> f32 {
-        self.z += self.a * (x - self.z);
-        flush_denormals(self.z)
+    pub fn process(&mut self, x: S) -> S {
+        self.z += (x - self.z) * self.a;
+        self.z.flush_denormals()
     }
 }
 
 /// One-pole high-pass built from an internal LP: hp = x - lp.
-pub struct OnePoleHp {
-    lp: OnePoleLp,
+pub struct OnePoleHp<S: Sample = f32> {
+    lp: OnePoleLp<S>,
 }
 
-impl OnePoleHp {
+impl<S: Sample> OnePoleHp<S> {
     pub fn new() -> Self {
         Self {
             lp: OnePoleLp::new(),
@@ -42,9 +45,9 @@ impl OnePoleHp {
     }
 
     #[inline]
-    pub fn process(&mut self, x: f32) -> f32 {
+    pub fn process(&mut self, x: S) -> S {
         let lp = self.lp.process(x);
-        flush_denormals(x - lp)
+        (x - lp).flush_denormals()
     }
 }
 
@@ -52,12 +55,12 @@ impl OnePoleHp {
 /// then applies opposite gains to low vs high.
 ///
 /// `tilt` in [-1, 1]: -1 = darker (more lows), +1 = brighter (more highs).
-pub struct TiltEq {
-    lp: OnePoleLp,
+pub struct TiltEq<S: Sample = f32> {
+    lp: OnePoleLp<S>,
     strength: f32,
 }
 
-impl TiltEq {
+impl<S: Sample> TiltEq<S> {
     pub fn new() -> Self {
         Self {
             lp: OnePoleLp::new(),
@@ -70,7 +73,7 @@ impl TiltEq {
     }
 
     #[inline]
-    pub fn process(&mut self, x: f32, tilt: f32) -> f32 {
+    pub fn process(&mut self, x: S, tilt: f32) -> S {
         let tilt = tilt.clamp(-1.0, 1.0);
         let low = self.lp.process(x);
         let high = x - low;
@@ -80,6 +83,6 @@ impl TiltEq {
         let low_gain = 1.0 + (-k);
         let high_gain = 1.0 + k;
 
-        flush_denormals(low * low_gain + high * high_gain)
+        (low * low_gain + high * high_gain).flush_denormals()
     }
 }
@@ -0,0 +1,142 @@
+use crate::dsp::fft::Complex;
+use crate::dsp::rng::XorShift32;
+use crate::dsp::stft::Stft;
+use crate::dsp::{flush_denormals, time_to_coeff};
+use crate::values::ParamValues;
+use core::f32::consts::TAU;
+
+const FFT_SIZE: usize = 2048;
+
+struct Channel {
+    stft: Stft,
+    /// Magnitudes we're fading away from.
+    old: Vec<f32>,
+    /// Most recently captured magnitudes.
+    new: Vec<f32>,
+    capture: bool,
+    xfade: f32,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            stft: Stft::new(FFT_SIZE),
+            old: vec![0.0; FFT_SIZE / 2 + 1],
+            new: vec![0.0; FFT_SIZE / 2 + 1],
+            capture: false,
+            xfade: 1.0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.stft.reset();
+        self.old.fill(0.0);
+        self.new.fill(0.0);
+        self.capture = false;
+        self.xfade = 1.0;
+    }
+
+    #[inline]
+    fn process(&mut self, x: f32, xfade_step: f32, rng: &mut XorShift32) -> f32 {
+        let Self {
+            stft,
+            old,
+            new,
+            capture,
+            xfade,
+        } = self;
+
+        stft.process(x, |spectrum| {
+            if *capture {
+                // whatever is audible right now becomes the fade-out side
+                for ((o, n), bin) in old.iter_mut().zip(new.iter_mut()).zip(spectrum.iter()) {
+                    *o += (*n - *o) * *xfade;
+                    *n = bin.norm();
+                }
+                *xfade = 0.0;
+                *capture = false;
+            }
+            *xfade = (*xfade + xfade_step).min(1.0);
+
+            // Resynthesize the held magnitudes with fresh random phases
+            for ((bin, o), n) in spectrum.iter_mut().zip(old.iter()).zip(new.iter()) {
+                let mag = o + (n - o) * *xfade;
+                *bin = Complex::from_polar(mag, rng.next_f32() * TAU);
+            }
+        })
+    }
+}
+
+/// Spectral freeze: on each trigger the current spectrum is grabbed and held
+/// as an endless pad layered over the live signal. Successive freezes
+/// crossfade into each other.
+pub struct Freeze {
+    sr: f32,
+    left: Channel,
+    right: Channel,
+    rng: XorShift32,
+    was_held: bool,
+    env: f32,
+    env_coeff: f32,
+    xfade_step: f32,
+}
+
+impl Freeze {
+    pub fn new(sr: f32) -> Self {
+        Self {
+            sr,
+            left: Channel::new(),
+            right: Channel::new(),
+            rng: XorShift32::new(0xF4EE_2E00),
+            was_held: false,
+            env: 0.0,
+            env_coeff: 0.0,
+            xfade_step: 1.0,
+        }
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        self.left.reset();
+        self.right.reset();
+        self.was_held = false;
+        self.env = 0.0;
+    }
+
+    /// Called once per processing block.
+    pub fn update_params(&mut self, sr: f32, p: &ParamValues) {
+        self.sr = sr;
+        let fade_s = p.freeze_fade_ms * 0.001;
+        self.env_coeff = time_to_coeff(fade_s, self.sr);
+        // spectra only change once per hop
+        self.xfade_step = self.left.stft.hop() as f32 / (fade_s * self.sr).max(1.0);
+    }
+
+    #[inline]
+    pub fn process(&mut self, l: f32, r: f32, p: &ParamValues) -> (f32, f32) {
+        let level = p.freeze_level;
+        if level <= 0.0 {
+            return (l, r);
+        }
+
+        let held = p.freeze_on;
+        if held && !self.was_held {
+            self.left.capture = true;
+            self.right.capture = true;
+        }
+        self.was_held = held;
+
+        let pad_l = self.left.process(l, self.xfade_step, &mut self.rng);
+        let pad_r = self.right.process(r, self.xfade_step, &mut self.rng);
+
+        // Pad swells in while held and fades out on release
+        let target = if held { 1.0 } else { 0.0 };
+        self.env = target + (self.env - target) * self.env_coeff;
+
+        let g = self.env * level;
+        (
+            flush_denormals(l + pad_l * g),
+            flush_denormals(r + pad_r * g),
+        )
+    }
+}
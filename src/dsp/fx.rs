@@ -1,5 +1,15 @@
-use crate::dsp::{fast_tanh, flush_denormals, time_to_coeff};
-use crate::params::GtrParams;
+use crate::dsp::crusher::Crusher;
+use crate::dsp::doubler::Doubler;
+use crate::dsp::freeze::Freeze;
+use crate::dsp::mix::DryWetMix;
+use crate::dsp::shapers::{Adaa1, Shaper};
+use crate::dsp::shimmer::{Shimmer, MAX_LOOP_DELAY_S};
+use crate::dsp::slots::SlotOrder;
+use crate::dsp::stutter::BeatRepeat;
+use crate::dsp::tapestop::TapeStop;
+use crate::dsp::{flush_denormals, time_to_coeff};
+use crate::params::{CrushPlacement, FxModule, SpaceMode, DEFAULT_FX_ORDER, FX_SLOTS};
+use crate::values::ParamValues;
 
 /// Simple circular delay line for basic space/reverb-ish effect.
 struct DelayLine {
@@ -15,6 +25,11 @@ impl DelayLine {
         }
     }
 
+    fn reset(&mut self) {
+        self.buf.fill(0.0);
+        self.idx = 0;
+    }
+
     #[inline]
     fn process(&mut self, x: f32, feedback: f32) -> f32 {
         let y = self.buf[self.idx];
@@ -74,13 +89,51 @@ impl Limiter {
     }
 }
 
-/// Stereo FX block: handles "Space" (simple feedback delay/reverb),
-/// "Width" (M/S widening), and safety limiter / output gain.
+/// Width via M/S.
+#[inline]
+fn width(l: f32, r: f32, p: &ParamValues) -> (f32, f32) {
+    let width = p.width.clamp(0.0, 1.0);
+
+    let mid = 0.5 * (l + r);
+    let side = 0.5 * (l - r);
+    let side_gain = 1.0 + width * 1.5; // up to ~+3.5dB side
+    let new_side = side * side_gain;
+
+    (mid + new_side, mid - new_side)
+}
+
+/// Peak level below which an inactive Space engine counts as silent.
+const SPACE_TAIL_FLOOR: f32 = 1.0e-5;
+/// How long an inactive Space engine must stay silent before it stops
+/// running: twice the longest a sample can stay in the shimmer loop (the
+/// echo lines are shorter), so nothing still in a line or the pitch shifter
+/// surfaces after it has stopped.
+const SPACE_TAIL_HOLD_S: f32 = 2.0 * MAX_LOOP_DELAY_S;
+
+/// Stereo FX block: beat repeat, tape stop, doubler, freeze, "Space" (simple
+/// feedback delay or shimmer reverb) and "Width" (M/S widening) in a
+/// reorderable slot chain, then post-FX crusher, global dry/wet and output
+/// gain / safety limiter.
 pub struct StereoFx {
     sr: f32,
+    stutter: BeatRepeat,
+    tapestop: TapeStop,
+    doubler: Doubler,
+    freeze: Freeze,
     delay_l: DelayLine,
     delay_r: DelayLine,
+    shimmer: Shimmer,
+    /// Mode the Space slot ran in on the previous sample.
+    space_mode: SpaceMode,
+    /// Samples the inactive Space engine has been silent for.
+    space_quiet: u32,
+    crush_l: Crusher,
+    crush_r: Crusher,
+    mix: DryWetMix,
     limiter: Limiter,
+    clip_l: Adaa1,
+    clip_r: Adaa1,
+    order: SlotOrder<FxModule, FX_SLOTS>,
 }
 
 impl StereoFx {
@@ -91,59 +144,184 @@ impl StereoFx {
 
         Self {
             sr,
+            stutter: BeatRepeat::new(sr),
+            tapestop: TapeStop::new(sr),
+            doubler: Doubler::new(sr),
+            freeze: Freeze::new(sr),
             delay_l: DelayLine::new(dl),
             delay_r: DelayLine::new(dr),
+            shimmer: Shimmer::new(sr),
+            space_mode: SpaceMode::Echo,
+            space_quiet: u32::MAX,
+            crush_l: Crusher::new(sr, &[0x1234_5678]),
+            crush_r: Crusher::new(sr, &[0x8765_4321]),
+            mix: DryWetMix::new(sr),
             limiter: Limiter::new(sr),
+            clip_l: Adaa1::new(Shaper::Soft),
+            clip_r: Adaa1::new(Shaper::Soft),
+            order: SlotOrder::new(sr, DEFAULT_FX_ORDER),
         }
     }
 
+    /// Allocates sample-rate dependent buffers, then resets. Must not be
+    /// called from the audio thread.
+    pub fn initialize(&mut self, sr: f32) {
+        self.delay_l = DelayLine::new((0.040 * sr) as usize);
+        self.delay_r = DelayLine::new((0.047 * sr) as usize);
+        self.stutter.initialize(sr);
+        self.tapestop.initialize(sr);
+        self.doubler.initialize(sr);
+        self.shimmer.initialize(sr);
+        self.reset(sr);
+    }
+
+    /// Clears state without allocating; the audio thread may call this.
     pub fn reset(&mut self, sr: f32) {
         self.sr = sr;
-        self.delay_l = DelayLine::new((0.040 * sr as f32) as usize);
-        self.delay_r = DelayLine::new((0.047 * sr as f32) as usize);
+        self.stutter.reset(sr);
+        self.tapestop.reset(sr);
+        self.doubler.reset(sr);
+        self.freeze.reset(sr);
+        self.delay_l.reset();
+        self.delay_r.reset();
+        self.shimmer.reset(sr);
+        self.space_quiet = u32::MAX;
+        self.crush_l.reset(sr);
+        self.crush_r.reset(sr);
+        self.mix.reset(sr);
         self.limiter.update_params(sr);
+        self.clip_l.reset();
+        self.clip_r.reset();
+        self.order.reset(sr);
     }
 
-    #[inline]
-    pub fn process_frame(&mut self, dry_l: f32, dry_r: f32, p: &GtrParams) -> (f32, f32) {
-        let space = p.space.value().clamp(0.0, 1.0);
-        let width = p.width.value().clamp(0.0, 1.0);
+    /// Called once per processing block to update filter coefficients etc.
+    pub fn update_params(&mut self, p: &ParamValues) {
+        self.freeze.update_params(self.sr, p);
+        self.shimmer.update_params(self.sr, p);
+        self.crush_l.update_params(self.sr, p);
+        self.crush_r.update_params(self.sr, p);
+        self.order.set_target(p.fx_order());
+    }
+
+    /// Latency of the guitar chain, in samples, for aligning the dry signal.
+    /// Called once per processing block.
+    pub fn set_dry_latency(&mut self, samples: f32) {
+        self.mix.set_latency(samples);
+    }
+
+    /// Processing delay of the whole plugin, in samples, given the guitar
+    /// chain's.
+    pub fn latency(&self, chain_latency: f32) -> f32 {
+        // the safety clipper's ADAA
+        chain_latency + 0.5
+    }
+
+    /// Called once per processing block with the host transport info.
+    pub fn set_transport(&mut self, tempo: Option<f64>, pos_beats: Option<f64>, playing: bool) {
+        self.stutter.set_transport(tempo, pos_beats, playing);
+    }
 
-        // Basic stereo feedback delay network
+    /// Basic stereo feedback delay network.
+    #[inline]
+    fn echo(&mut self, l: f32, r: f32, space: f32) -> (f32, f32) {
         let fb = 0.25 + 0.4 * space; // more feedback with higher space
 
-        let dl = self.delay_l.process(dry_l, fb);
-        let dr = self.delay_r.process(dry_r, fb);
+        let dl = self.delay_l.process(l, fb);
+        let dr = self.delay_r.process(r, fb);
 
         // crossfeed to make it a bit more spacious
-        let wet_l = dl + 0.3 * dr;
-        let wet_r = dr + 0.3 * dl;
+        (dl + 0.3 * dr, dr + 0.3 * dl)
+    }
+
+    /// Echo or shimmer, mixed with the dry signal by the Space amount.
+    ///
+    /// After a mode switch the other engine keeps running on silence until
+    /// its tail has died away, so switching doesn't cut off the repeats.
+    #[inline]
+    fn space(&mut self, dry_l: f32, dry_r: f32, p: &ParamValues) -> (f32, f32) {
+        let space = p.space.clamp(0.0, 1.0);
+
+        let (wet_l, wet_r) = match p.space_mode {
+            SpaceMode::Echo => self.echo(dry_l, dry_r, space),
+            SpaceMode::Shimmer => self.shimmer.process(dry_l, dry_r, p),
+        };
+
+        if p.space_mode != self.space_mode {
+            // the engine that was just playing becomes the tail
+            self.space_mode = p.space_mode;
+            self.space_quiet = 0;
+        }
+
+        let hold = (SPACE_TAIL_HOLD_S * self.sr) as u32;
+        let (tail_l, tail_r) = if self.space_quiet < hold {
+            let (tl, tr) = match p.space_mode {
+                SpaceMode::Echo => self.shimmer.process(0.0, 0.0, p),
+                SpaceMode::Shimmer => self.echo(0.0, 0.0, space),
+            };
+            if tl.abs().max(tr.abs()) < SPACE_TAIL_FLOOR {
+                self.space_quiet += 1;
+            } else {
+                self.space_quiet = 0;
+            }
+            (tl, tr)
+        } else {
+            (0.0, 0.0)
+        };
 
         let mix = space; // 0..1
-        let mut l = dry_l * (1.0 - mix) + wet_l * mix;
-        let mut r = dry_r * (1.0 - mix) + wet_r * mix;
+        (
+            dry_l * (1.0 - mix) + (wet_l + tail_l) * mix,
+            dry_r * (1.0 - mix) + (wet_r + tail_r) * mix,
+        )
+    }
+
+    #[inline]
+    /// `in_l`/`in_r` is the clean plugin input, `l`/`r` the guitar chain output.
+    pub fn process_frame(
+        &mut self,
+        in_l: f32,
+        in_r: f32,
+        mut l: f32,
+        mut r: f32,
+        p: &ParamValues,
+    ) -> (f32, f32) {
+        let (order, gain) = self.order.next();
+        for module in order {
+            (l, r) = match module {
+                FxModule::Stutter => self.stutter.process(l, r, p),
+                FxModule::TapeStop => self.tapestop.process(l, r, p),
+                FxModule::Doubler => self.doubler.process(l, r, p),
+                FxModule::Freeze => self.freeze.process(l, r, p),
+                FxModule::Space => self.space(l, r, p),
+                FxModule::Width => width(l, r, p),
+            };
+        }
 
-        // Width via M/S
-        let mid = 0.5 * (l + r);
-        let side = 0.5 * (l - r);
-        let side_gain = 1.0 + width * 1.5; // up to ~+3.5dB side
-        let new_side = side * side_gain;
+        // Dips briefly while the slot order changes
+        l *= gain;
+        r *= gain;
+
+        if p.crush_placement == CrushPlacement::PostFx {
+            l = self.crush_l.process_sample(l, p);
+            r = self.crush_r.process_sample(r, p);
+        }
 
-        l = mid + new_side;
-        r = mid - new_side;
+        // Blend with the clean input
+        (l, r) = self.mix.process(in_l, in_r, l, r, p);
 
         // Apply output gain then limiter if enabled
         let out_gain = p.output_linear();
         l *= out_gain;
         r *= out_gain;
 
-        if p.limiter_on.value() {
+        if p.limiter_on {
             (l, r) = self.limiter.process(l, r);
         }
 
-        // Final soft clipper as safety
-        let l = fast_tanh(l);
-        let r = fast_tanh(r);
+        // Final soft clipper as safety, anti-aliased
+        let l = self.clip_l.process(l);
+        let r = self.clip_r.process(r);
 
         (flush_denormals(l), flush_denormals(r))
     }
@@ -0,0 +1,215 @@
+use crate::dsp::{flush_denormals, time_to_coeff};
+use crate::params::MixLaw;
+use crate::values::ParamValues;
+use core::f32::consts::FRAC_PI_2;
+
+/// Dry delay line length. Power of two, longer than any chain latency.
+const DRY_LEN: usize = 8;
+/// Bypass crossfade time.
+const BYPASS_FADE_S: f32 = 0.005;
+/// How long tails may ring on while bypassed. Longer than the longest
+/// shimmer decay; a held freeze or beat repeat would ring forever.
+const BYPASS_TAIL_S: f32 = 8.0;
+/// Fade-out of whatever is still ringing after `BYPASS_TAIL_S`.
+const BYPASS_TAIL_FADE_S: f32 = 2.0;
+
+/// Short fractional delay that lines the clean input up with the processed
+/// signal (every ADAA shaper costs half a sample), so blends don't comb.
+struct DryDelay {
+    buf_l: [f32; DRY_LEN],
+    buf_r: [f32; DRY_LEN],
+    pos: usize,
+    latency: f32,
+}
+
+impl DryDelay {
+    fn new() -> Self {
+        Self {
+            buf_l: [0.0; DRY_LEN],
+            buf_r: [0.0; DRY_LEN],
+            pos: 0,
+            latency: 0.0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.buf_l = [0.0; DRY_LEN];
+        self.buf_r = [0.0; DRY_LEN];
+        self.pos = 0;
+    }
+
+    fn set_latency(&mut self, samples: f32) {
+        self.latency = samples.clamp(0.0, (DRY_LEN - 2) as f32);
+    }
+
+    #[inline]
+    fn process(&mut self, l: f32, r: f32) -> (f32, f32) {
+        self.pos = (self.pos + 1) % DRY_LEN;
+        self.buf_l[self.pos] = l;
+        self.buf_r[self.pos] = r;
+
+        // Linear interpolation between the two taps around the latency
+        let whole = self.latency as usize;
+        let frac = self.latency - whole as f32;
+        let i0 = (self.pos + DRY_LEN - whole) % DRY_LEN;
+        let i1 = (i0 + DRY_LEN - 1) % DRY_LEN;
+        (
+            self.buf_l[i0] + (self.buf_l[i1] - self.buf_l[i0]) * frac,
+            self.buf_r[i0] + (self.buf_r[i1] - self.buf_r[i0]) * frac,
+        )
+    }
+}
+
+/// Global dry/wet blend between the clean input and the processed signal.
+pub struct DryWetMix {
+    dry: DryDelay,
+    mix: f32,
+    mix_coeff: f32,
+}
+
+impl DryWetMix {
+    pub fn new(sr: f32) -> Self {
+        let mut m = Self {
+            dry: DryDelay::new(),
+            mix: 1.0,
+            mix_coeff: 0.0,
+        };
+        m.reset(sr);
+        m
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.dry.reset();
+        self.mix_coeff = time_to_coeff(0.01, sr);
+    }
+
+    /// Latency of the processed path, in samples. Called once per block.
+    pub fn set_latency(&mut self, samples: f32) {
+        self.dry.set_latency(samples);
+    }
+
+    #[inline]
+    pub fn process(
+        &mut self,
+        dry_l: f32,
+        dry_r: f32,
+        wet_l: f32,
+        wet_r: f32,
+        p: &ParamValues,
+    ) -> (f32, f32) {
+        let (dl, dr) = self.dry.process(dry_l, dry_r);
+
+        let target = p.mix.clamp(0.0, 1.0);
+        self.mix = target + (self.mix - target) * self.mix_coeff;
+
+        let (dry_gain, wet_gain) = match p.mix_law {
+            MixLaw::Linear => (1.0 - self.mix, self.mix),
+            // constant power: -3 dB each in the middle
+            MixLaw::EqualPower => ((self.mix * FRAC_PI_2).cos(), (self.mix * FRAC_PI_2).sin()),
+        };
+        let dry_gain = if p.kill_dry { 0.0 } else { dry_gain };
+
+        (
+            flush_denormals(dl * dry_gain + wet_l * wet_gain),
+            flush_denormals(dr * dry_gain + wet_r * wet_gain),
+        )
+    }
+}
+
+/// Host bypass. Engaging it fades the input to the processing out and the
+/// latency-aligned dry signal in, so delay/reverb tails keep ringing out on
+/// top of the dry signal instead of being cut. After `BYPASS_TAIL_S` the
+/// processed signal fades out too.
+pub struct Bypass {
+    dry: DryDelay,
+    amount: f32,
+    step: f32,
+    /// Gain on the processed signal, dropped once the tail time is up.
+    wet: f32,
+    tail_step: f32,
+    /// Samples left until the tail fades out, while fully bypassed.
+    tail_left: u32,
+    tail_len: u32,
+}
+
+impl Bypass {
+    pub fn new(sr: f32) -> Self {
+        let mut b = Self {
+            dry: DryDelay::new(),
+            amount: 0.0,
+            step: 1.0,
+            wet: 1.0,
+            tail_step: 1.0,
+            tail_left: 0,
+            tail_len: 0,
+        };
+        b.reset(sr);
+        b
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.dry.reset();
+        self.step = 1.0 / (BYPASS_FADE_S * sr).max(1.0);
+        self.tail_step = 1.0 / (BYPASS_TAIL_FADE_S * sr).max(1.0);
+        self.tail_len = (BYPASS_TAIL_S * sr) as u32;
+        self.tail_left = self.tail_len;
+    }
+
+    /// Latency of the whole plugin, in samples. Called once per block.
+    pub fn set_latency(&mut self, samples: f32) {
+        self.dry.set_latency(samples);
+    }
+
+    /// Advances the crossfade by one sample. Returns the gain for the signal
+    /// going into the processing, the gain for the signal coming out of it,
+    /// and the dry signal to add to its output.
+    #[inline]
+    pub fn process(&mut self, l: f32, r: f32, bypassed: bool) -> (f32, f32, (f32, f32)) {
+        if bypassed {
+            self.amount = (self.amount + self.step).min(1.0);
+        } else {
+            self.amount = (self.amount - self.step).max(0.0);
+        }
+
+        if !bypassed {
+            self.tail_left = self.tail_len;
+            self.wet = (self.wet + self.step).min(1.0);
+        } else if self.amount >= 1.0 {
+            if self.tail_left > 0 {
+                self.tail_left -= 1;
+            } else {
+                self.wet = (self.wet - self.tail_step).max(0.0);
+            }
+        }
+
+        let (dl, dr) = self.dry.process(l, r);
+        (
+            1.0 - self.amount,
+            self.wet,
+            (dl * self.amount, dr * self.amount),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bypass_tail_fades_out_and_comes_back() {
+        let sr = 48000.0;
+        let mut bypass = Bypass::new(sr);
+
+        let wet_after = |bypass: &mut Bypass, bypassed: bool, seconds: f32| {
+            let mut wet = 0.0;
+            for _ in 0..(seconds * sr) as usize {
+                (_, wet, _) = bypass.process(0.0, 0.0, bypassed);
+            }
+            wet
+        };
+
+        assert_eq!(wet_after(&mut bypass, true, BYPASS_TAIL_S - 0.1), 1.0);
+        assert_eq!(wet_after(&mut bypass, true, BYPASS_TAIL_FADE_S + 0.2), 0.0);
+        assert_eq!(wet_after(&mut bypass, false, 2.0 * BYPASS_FADE_S), 1.0);
+    }
+}
@@ -1,9 +1,26 @@
 pub mod amp;
 pub mod cab;
 pub mod chain;
+pub mod crusher;
+pub mod doubler;
+pub mod fft;
 pub mod filters;
+pub mod freeze;
 pub mod fx;
 pub mod gate;
+pub mod mix;
+pub mod nam;
+pub mod poweramp;
+pub mod rng;
+pub mod shapers;
+pub mod shimmer;
+pub mod simd;
+pub mod slots;
+pub mod stft;
+pub mod stutter;
+pub mod tapestop;
+pub mod tonestack;
+pub mod transient;
 
 pub use chain::GuitarChain;
 pub use fx::StereoFx;
@@ -0,0 +1,141 @@
+use serde::Deserialize;
+
+use super::Weights;
+
+#[derive(Deserialize)]
+pub struct LstmConfig {
+    pub num_layers: usize,
+    pub input_size: usize,
+    pub hidden_size: usize,
+}
+
+#[inline]
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[derive(Clone)]
+struct Cell {
+    input: usize,
+    hidden: usize,
+    /// (4 * hidden) x (input + hidden), row-major. Gate order i, f, g, o.
+    w: Vec<f32>,
+    b: Vec<f32>,
+    /// Concatenated [x, h].
+    xh: Vec<f32>,
+    c: Vec<f32>,
+    h0: Vec<f32>,
+    c0: Vec<f32>,
+    ifgo: Vec<f32>,
+}
+
+impl Cell {
+    fn load(input: usize, hidden: usize, weights: &mut Weights) -> Result<Self, String> {
+        let w = weights.take(4 * hidden * (input + hidden))?;
+        let b = weights.take(4 * hidden)?;
+        let h0 = weights.take(hidden)?;
+        let c0 = weights.take(hidden)?;
+
+        let mut xh = vec![0.0; input + hidden];
+        xh[input..].copy_from_slice(&h0);
+        Ok(Self {
+            input,
+            hidden,
+            w,
+            b,
+            xh,
+            c: c0.clone(),
+            h0,
+            c0,
+            ifgo: vec![0.0; 4 * hidden],
+        })
+    }
+
+    fn reset(&mut self) {
+        self.xh.fill(0.0);
+        self.xh[self.input..].copy_from_slice(&self.h0);
+        self.c.copy_from_slice(&self.c0);
+    }
+
+    #[inline]
+    fn step(&mut self) {
+        let cols = self.input + self.hidden;
+        for (row, (out, bias)) in self.ifgo.iter_mut().zip(&self.b).enumerate() {
+            let w = &self.w[row * cols..(row + 1) * cols];
+            *out = bias + w.iter().zip(&self.xh).map(|(a, b)| a * b).sum::<f32>();
+        }
+
+        let h = self.hidden;
+        for j in 0..h {
+            let i = sigmoid(self.ifgo[j]);
+            let f = sigmoid(self.ifgo[h + j]);
+            let g = self.ifgo[2 * h + j].tanh();
+            let o = sigmoid(self.ifgo[3 * h + j]);
+            self.c[j] = f * self.c[j] + i * g;
+            self.xh[self.input + j] = o * self.c[j].tanh();
+        }
+    }
+
+    fn hidden_state(&self) -> &[f32] {
+        &self.xh[self.input..]
+    }
+}
+
+/// Stacked LSTM with a linear head, as exported by Neural Amp Modeler.
+#[derive(Clone)]
+pub struct Lstm {
+    cells: Vec<Cell>,
+    head_w: Vec<f32>,
+    head_b: f32,
+}
+
+impl Lstm {
+    pub fn load(config: &LstmConfig, weights: &mut Weights) -> Result<Self, String> {
+        if config.num_layers == 0 || config.input_size != 1 {
+            return Err("unsupported LSTM shape".to_string());
+        }
+        let mut cells = Vec::with_capacity(config.num_layers);
+        for i in 0..config.num_layers {
+            let input = if i == 0 {
+                config.input_size
+            } else {
+                config.hidden_size
+            };
+            cells.push(Cell::load(input, config.hidden_size, weights)?);
+        }
+        let head_w = weights.take(config.hidden_size)?;
+        let head_b = weights.take(1)?[0];
+        Ok(Self {
+            cells,
+            head_w,
+            head_b,
+        })
+    }
+
+    pub fn reset(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.reset();
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.cells[0].xh[0] = x;
+        self.cells[0].step();
+        for i in 1..self.cells.len() {
+            let (done, rest) = self.cells.split_at_mut(i);
+            let prev = done[i - 1].hidden_state();
+            rest[0].xh[..prev.len()].copy_from_slice(prev);
+            rest[0].step();
+        }
+
+        let last = self.cells[self.cells.len() - 1].hidden_state();
+        self.head_b
+            + self
+                .head_w
+                .iter()
+                .zip(last)
+                .map(|(a, b)| a * b)
+                .sum::<f32>()
+    }
+}
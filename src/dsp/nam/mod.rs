@@ -0,0 +1,378 @@
+//! Neural Amp Modeler (`.nam`) model loading and CPU inference.
+//!
+//! Supports the WaveNet and LSTM architectures. Everything is allocated when
+//! a model is loaded; `process` only touches preallocated buffers. Models run
+//! at the host sample rate without resampling, so a file that states a
+//! different training rate is rejected rather than played off-pitch.
+
+mod lstm;
+mod wavenet;
+
+use nih_plug::nih_log;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lstm::{Lstm, LstmConfig};
+use wavenet::{WaveNet, WaveNetConfig};
+
+/// Loudness NAM models are normalized to when the file reports its own.
+const TARGET_LOUDNESS_DB: f32 = -18.0;
+/// Zero samples run through a fresh model so its state settles.
+const PREWARM_SAMPLES: usize = 4096;
+/// Most `.nam` files the Neural Model param can pick from.
+pub const MAX_NAM_MODELS: usize = 32;
+
+#[derive(Deserialize)]
+struct NamFile {
+    architecture: String,
+    config: serde_json::Value,
+    weights: Vec<f32>,
+    #[serde(default)]
+    sample_rate: Option<f32>,
+    #[serde(default)]
+    metadata: Option<NamMetadata>,
+}
+
+#[derive(Deserialize)]
+struct NamMetadata {
+    #[serde(default)]
+    loudness: Option<f32>,
+}
+
+/// Sequential reader over the flat weight list.
+pub(crate) struct Weights<'a> {
+    it: std::slice::Iter<'a, f32>,
+}
+
+impl Weights<'_> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<Vec<f32>, String> {
+        let v: Vec<f32> = self.it.by_ref().take(n).copied().collect();
+        if v.len() == n {
+            Ok(v)
+        } else {
+            Err("not enough weights for the model config".to_string())
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Net {
+    Lstm(Lstm),
+    WaveNet(WaveNet),
+}
+
+#[derive(Clone)]
+pub struct NeuralModel {
+    net: Net,
+    /// Sample rate the model was trained at, if the file says.
+    pub sample_rate: Option<f32>,
+    /// Linear gain that brings the model to the target loudness.
+    pub normalize: f32,
+}
+
+impl NeuralModel {
+    /// Parses and builds a model from a `.nam` file for a host running at
+    /// `sample_rate`. Blocking; call it from a background thread.
+    pub fn load(path: &str, sample_rate: f32) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        Self::parse(&text, sample_rate).map_err(|e| format!("{path}: {e}"))
+    }
+
+    /// `load` from the file's contents.
+    fn parse(text: &str, sample_rate: f32) -> Result<Self, String> {
+        let file: NamFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if let Some(rate) = file
+            .sample_rate
+            .filter(|&rate| !rate_matches(rate, sample_rate))
+        {
+            return Err(format!(
+                "trained at {rate} Hz but the host runs at {sample_rate} Hz"
+            ));
+        }
+
+        let mut weights = Weights {
+            it: file.weights.iter(),
+        };
+        let net = match file.architecture.as_str() {
+            "LSTM" => {
+                let cfg: LstmConfig =
+                    serde_json::from_value(file.config).map_err(|e| e.to_string())?;
+                Net::Lstm(Lstm::load(&cfg, &mut weights)?)
+            }
+            "WaveNet" => {
+                let cfg: WaveNetConfig =
+                    serde_json::from_value(file.config).map_err(|e| e.to_string())?;
+                Net::WaveNet(WaveNet::load(&cfg, &mut weights)?)
+            }
+            other => return Err(format!("unsupported architecture {other}")),
+        };
+        if weights.it.len() != 0 {
+            return Err("more weights than the model config uses".to_string());
+        }
+
+        let normalize = file
+            .metadata
+            .and_then(|m| m.loudness)
+            .map_or(1.0, |loudness| {
+                crate::params::db_to_gain(TARGET_LOUDNESS_DB - loudness)
+            });
+
+        let mut model = Self {
+            net,
+            sample_rate: file.sample_rate,
+            normalize,
+        };
+        // let the state settle on silence
+        for _ in 0..PREWARM_SAMPLES {
+            model.process(0.0);
+        }
+        Ok(model)
+    }
+
+    /// Whether the model may run at `sample_rate`. Files that don't say
+    /// what they were trained at are assumed to fit.
+    pub fn runs_at(&self, sample_rate: f32) -> bool {
+        match self.sample_rate {
+            Some(rate) => rate_matches(rate, sample_rate),
+            None => true,
+        }
+    }
+
+    /// Clears the model state back to what the file specifies.
+    pub fn reset(&mut self) {
+        match &mut self.net {
+            Net::Lstm(n) => n.reset(),
+            Net::WaveNet(n) => n.reset(),
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        match &mut self.net {
+            Net::Lstm(n) => n.process(x),
+            Net::WaveNet(n) => n.process(x),
+        }
+    }
+}
+
+fn rate_matches(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1.0
+}
+
+/// `*.nam` files in `dir`, sorted by file name, at most `MAX_NAM_MODELS`.
+/// Blocking; keep it off the audio thread.
+pub fn list_models(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "nam"))
+        .collect();
+    paths.sort();
+
+    if paths.len() > MAX_NAM_MODELS {
+        for path in &paths[MAX_NAM_MODELS..] {
+            nih_log!(
+                "Skipping neural amp model {}: only the first {MAX_NAM_MODELS} can be selected",
+                path.display()
+            );
+        }
+        paths.truncate(MAX_NAM_MODELS);
+    }
+    paths
+}
+
+type ModelPair = (Box<NeuralModel>, Box<NeuralModel>);
+type ModelSlots = (Option<Box<NeuralModel>>, Option<Box<NeuralModel>>);
+
+/// Hand-off between the background loader and the audio thread.
+///
+/// The loader parks a freshly built (left, right) pair in `pending`; the
+/// audio thread picks it up with `try_lock` and parks the models it replaced
+/// in `retired`, which the loader drops on its next run. Nothing is allocated
+/// or freed on the audio thread, and it never blocks.
+#[derive(Default)]
+pub struct NeuralSlot {
+    pending: Mutex<Option<ModelPair>>,
+    retired: Mutex<Option<ModelSlots>>,
+}
+
+impl NeuralSlot {
+    /// Background thread: load `path` for a host running at `sample_rate`
+    /// and queue it for the audio thread.
+    pub fn load(&self, path: &str, sample_rate: f32) -> Result<(), String> {
+        // free whatever the audio thread swapped out last time
+        let old = self.retired.lock().ok().and_then(|mut r| r.take());
+        drop(old);
+
+        let model = NeuralModel::load(path, sample_rate)?;
+        let pair = (Box::new(model.clone()), Box::new(model));
+        if let Ok(mut pending) = self.pending.lock() {
+            *pending = Some(pair);
+        }
+        Ok(())
+    }
+
+    /// Drops a model that was loaded but not swapped in yet, e.g. because
+    /// the sample rate changed in the meantime. Not for the audio thread.
+    pub fn clear_pending(&self) {
+        let old = self.pending.lock().ok().and_then(|mut p| p.take());
+        drop(old);
+    }
+
+    /// Audio thread: swap in a pending model, if there is one and the
+    /// previous swap has been cleaned up.
+    pub fn try_swap(
+        &self,
+        left: &mut Option<Box<NeuralModel>>,
+        right: &mut Option<Box<NeuralModel>>,
+    ) {
+        let Ok(mut retired) = self.retired.try_lock() else {
+            return;
+        };
+        if retired.is_some() {
+            return;
+        }
+        let Ok(mut pending) = self.pending.try_lock() else {
+            return;
+        };
+        if let Some((l, r)) = pending.take() {
+            *retired = Some((left.replace(l), right.replace(r)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// One-cell LSTM: gates i, f, g, o take x at 1, 0.5, 2, -1 and ignore h,
+    /// no biases, h0 = 0, c0 = 0.5, head 2 h + 0.1.
+    fn lstm_file(weights: &[f32]) -> serde_json::Value {
+        json!({
+            "architecture": "LSTM",
+            "config": { "num_layers": 1, "input_size": 1, "hidden_size": 1 },
+            "weights": weights,
+            "sample_rate": 48000,
+        })
+    }
+
+    const LSTM_WEIGHTS: [f32; 16] = [
+        1.0, 0.0, 0.5, 0.0, 2.0, 0.0, -1.0, 0.0, // w
+        0.0, 0.0, 0.0, 0.0, // b
+        0.0, 0.5, // h0, c0
+        2.0, 0.1, // head
+    ];
+
+    /// One array with a single kernel-2 layer on one channel.
+    fn wavenet_file(weights: &[f32]) -> serde_json::Value {
+        json!({
+            "architecture": "WaveNet",
+            "config": {
+                "layers": [{
+                    "input_size": 1,
+                    "condition_size": 1,
+                    "head_size": 1,
+                    "channels": 1,
+                    "kernel_size": 2,
+                    "dilations": [1],
+                    "activation": "Tanh",
+                    "gated": false,
+                    "head_bias": true,
+                }],
+                "head": null,
+                "head_scale": 0.9,
+            },
+            "weights": weights,
+            "sample_rate": 48000,
+        })
+    }
+
+    const WAVENET_WEIGHTS: [f32; 10] = [
+        0.8, // rechannel
+        0.3, 0.5, 0.1, 0.2, // conv taps (previous, current), conv bias, mixin
+        0.7, 0.0, // 1x1
+        1.5, -0.05, // head rechannel
+        0.9,   // head scale
+    ];
+
+    fn parse(file: serde_json::Value) -> Result<NeuralModel, String> {
+        NeuralModel::parse(&file.to_string(), 48000.0)
+    }
+
+    fn sigmoid(x: f32) -> f32 {
+        1.0 / (1.0 + (-x).exp())
+    }
+
+    #[test]
+    fn lstm_step_matches_hand_computed_output() {
+        let mut model = parse(lstm_file(&LSTM_WEIGHTS)).unwrap();
+        model.reset();
+
+        let x = 0.5;
+        let c = sigmoid(0.5 * x) * 0.5 + sigmoid(x) * (2.0 * x).tanh();
+        let h = sigmoid(-x) * c.tanh();
+        let y = model.process(x);
+        assert!((y - (2.0 * h + 0.1)).abs() < 1e-6, "{y}");
+    }
+
+    #[test]
+    fn wavenet_steps_match_hand_computed_output() {
+        let mut model = parse(wavenet_file(&WAVENET_WEIGHTS)).unwrap();
+        model.reset();
+
+        // first sample: nothing in the history yet
+        let y = model.process(0.5);
+        let expect = 0.9 * (1.5 * (0.1 + 0.2 * 0.5 + 0.5 * 0.8 * 0.5f32).tanh() - 0.05);
+        assert!((y - expect).abs() < 1e-6, "{y} vs {expect}");
+
+        // second sample: the previous tap sees the first input
+        let y = model.process(0.0);
+        let expect = 0.9 * (1.5 * (0.1 + 0.3 * 0.8 * 0.5f32).tanh() - 0.05);
+        assert!((y - expect).abs() < 1e-6, "{y} vs {expect}");
+    }
+
+    #[test]
+    fn wrong_weight_count_is_rejected() {
+        for (file, weights) in [
+            (
+                lstm_file as fn(&[f32]) -> serde_json::Value,
+                &LSTM_WEIGHTS[..],
+            ),
+            (wavenet_file, &WAVENET_WEIGHTS[..]),
+        ] {
+            assert!(parse(file(weights)).is_ok());
+            assert!(parse(file(&weights[1..])).is_err());
+            let extra: Vec<f32> = weights.iter().copied().chain([0.0]).collect();
+            assert!(parse(file(&extra)).is_err());
+        }
+    }
+
+    #[test]
+    fn wrong_sample_rate_is_rejected() {
+        let file = lstm_file(&LSTM_WEIGHTS).to_string();
+        assert!(NeuralModel::parse(&file, 48000.0).is_ok());
+        assert!(NeuralModel::parse(&file, 44100.0).is_err());
+        let file = wavenet_file(&WAVENET_WEIGHTS).to_string();
+        assert!(NeuralModel::parse(&file, 96000.0).is_err());
+    }
+
+    /// Fails on any allocation when run with `--features assert_process_allocs`.
+    #[test]
+    fn process_does_not_allocate() {
+        let mut lstm = parse(lstm_file(&LSTM_WEIGHTS)).unwrap();
+        let mut wavenet = parse(wavenet_file(&WAVENET_WEIGHTS)).unwrap();
+        crate::no_alloc(|| {
+            for i in 0..1024 {
+                let x = (i as f32 * 0.01).sin();
+                assert!(lstm.process(x).is_finite());
+                assert!(wavenet.process(x).is_finite());
+            }
+            lstm.reset();
+            wavenet.reset();
+        });
+    }
+}
@@ -0,0 +1,316 @@
+use serde::Deserialize;
+
+use super::Weights;
+
+#[derive(Deserialize)]
+pub struct WaveNetConfig {
+    pub layers: Vec<LayerArrayConfig>,
+    #[serde(default)]
+    pub head: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+pub struct LayerArrayConfig {
+    pub input_size: usize,
+    pub condition_size: usize,
+    pub head_size: usize,
+    pub channels: usize,
+    pub kernel_size: usize,
+    pub dilations: Vec<usize>,
+    pub activation: String,
+    pub gated: bool,
+    pub head_bias: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Activation {
+    Tanh,
+    ReLU,
+    Sigmoid,
+    Hardtanh,
+}
+
+impl Activation {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "Tanh" | "Fasttanh" => Ok(Activation::Tanh),
+            "ReLU" => Ok(Activation::ReLU),
+            "Sigmoid" => Ok(Activation::Sigmoid),
+            "Hardtanh" => Ok(Activation::Hardtanh),
+            other => Err(format!("unsupported activation {other}")),
+        }
+    }
+
+    #[inline]
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Tanh => x.tanh(),
+            Activation::ReLU => x.max(0.0),
+            Activation::Sigmoid => sigmoid(x),
+            Activation::Hardtanh => x.clamp(-1.0, 1.0),
+        }
+    }
+}
+
+#[inline]
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+/// Pointwise (kernel size 1) convolution: `y = W x (+ b)`.
+#[derive(Clone)]
+struct Conv1x1 {
+    inputs: usize,
+    w: Vec<f32>,
+    b: Option<Vec<f32>>,
+}
+
+impl Conv1x1 {
+    fn load(
+        inputs: usize,
+        outputs: usize,
+        bias: bool,
+        weights: &mut Weights,
+    ) -> Result<Self, String> {
+        let w = weights.take(outputs * inputs)?;
+        let b = if bias {
+            Some(weights.take(outputs)?)
+        } else {
+            None
+        };
+        Ok(Self { inputs, w, b })
+    }
+
+    #[inline]
+    fn apply(&self, x: &[f32], y: &mut [f32]) {
+        for (o, out) in y.iter_mut().enumerate() {
+            let row = &self.w[o * self.inputs..(o + 1) * self.inputs];
+            let bias = self.b.as_ref().map_or(0.0, |b| b[o]);
+            *out = bias + row.iter().zip(x).map(|(a, b)| a * b).sum::<f32>();
+        }
+    }
+}
+
+/// One dilated, optionally gated, residual layer.
+#[derive(Clone)]
+struct Layer {
+    channels: usize,
+    kernel: usize,
+    dilation: usize,
+    gated: bool,
+    activation: Activation,
+    /// [kernel][out][in]
+    conv_w: Vec<f32>,
+    conv_b: Vec<f32>,
+    /// [out][condition]
+    mixin_w: Vec<f32>,
+    one: Conv1x1,
+    /// Ring of the last `(kernel - 1) * dilation + 1` input frames.
+    hist: Vec<f32>,
+    hist_len: usize,
+    hist_pos: usize,
+    conv_out: Vec<f32>,
+    act: Vec<f32>,
+    residual: Vec<f32>,
+}
+
+impl Layer {
+    fn load(
+        cfg: &LayerArrayConfig,
+        dilation: usize,
+        weights: &mut Weights,
+    ) -> Result<Self, String> {
+        let c = cfg.channels;
+        let out = if cfg.gated { 2 * c } else { c };
+        let k = cfg.kernel_size;
+
+        // NAM order: for out, for in, for k
+        let raw = weights.take(out * c * k)?;
+        let mut conv_w = vec![0.0; k * out * c];
+        for o in 0..out {
+            for i in 0..c {
+                for kk in 0..k {
+                    conv_w[(kk * out + o) * c + i] = raw[(o * c + i) * k + kk];
+                }
+            }
+        }
+        let conv_b = weights.take(out)?;
+        let mixin_w = weights.take(out * cfg.condition_size)?;
+        let one = Conv1x1::load(c, c, true, weights)?;
+
+        let hist_len = (k.max(1) - 1) * dilation + 1;
+        Ok(Self {
+            channels: c,
+            kernel: k,
+            dilation,
+            gated: cfg.gated,
+            activation: Activation::parse(&cfg.activation)?,
+            conv_w,
+            conv_b,
+            mixin_w,
+            one,
+            hist: vec![0.0; hist_len * c],
+            hist_len,
+            hist_pos: 0,
+            conv_out: vec![0.0; out],
+            act: vec![0.0; c],
+            residual: vec![0.0; c],
+        })
+    }
+
+    fn reset(&mut self) {
+        self.hist.fill(0.0);
+        self.hist_pos = 0;
+    }
+
+    /// `z` in, `z + 1x1(act(conv(z)))` out; activations are added to `head`.
+    #[inline]
+    fn process(&mut self, z: &mut [f32], condition: &[f32], head: &mut [f32]) {
+        let c = self.channels;
+        self.hist_pos = (self.hist_pos + 1) % self.hist_len;
+        self.hist[self.hist_pos * c..(self.hist_pos + 1) * c].copy_from_slice(z);
+
+        let out = self.conv_out.len();
+        for o in 0..out {
+            let mut acc = self.conv_b[o];
+            let mix = &self.mixin_w[o * condition.len()..(o + 1) * condition.len()];
+            acc += mix.iter().zip(condition).map(|(a, b)| a * b).sum::<f32>();
+
+            for kk in 0..self.kernel {
+                let lag = (self.kernel - 1 - kk) * self.dilation;
+                let frame = (self.hist_pos + self.hist_len - lag) % self.hist_len;
+                let x = &self.hist[frame * c..(frame + 1) * c];
+                let w = &self.conv_w[(kk * out + o) * c..(kk * out + o + 1) * c];
+                acc += w.iter().zip(x).map(|(a, b)| a * b).sum::<f32>();
+            }
+            self.conv_out[o] = acc;
+        }
+
+        let (pre, gate) = self.conv_out.split_at(c);
+        for (j, (act, h)) in self.act.iter_mut().zip(head.iter_mut()).enumerate() {
+            let a = self.activation.apply(pre[j]);
+            *act = if self.gated { a * sigmoid(gate[j]) } else { a };
+            *h += *act;
+        }
+
+        self.one.apply(&self.act, &mut self.residual);
+        for (zi, r) in z.iter_mut().zip(&self.residual) {
+            *zi += r;
+        }
+    }
+}
+
+#[derive(Clone)]
+struct LayerArray {
+    rechannel: Conv1x1,
+    layers: Vec<Layer>,
+    head_rechannel: Conv1x1,
+    /// Residual stream, `channels` wide. Also this array's output.
+    z: Vec<f32>,
+    /// Head accumulator, `channels` wide.
+    head: Vec<f32>,
+    /// Head output, `head_size` wide.
+    head_out: Vec<f32>,
+}
+
+impl LayerArray {
+    fn load(cfg: &LayerArrayConfig, weights: &mut Weights) -> Result<Self, String> {
+        let rechannel = Conv1x1::load(cfg.input_size, cfg.channels, false, weights)?;
+        let layers = cfg
+            .dilations
+            .iter()
+            .map(|&d| Layer::load(cfg, d, weights))
+            .collect::<Result<Vec<_>, _>>()?;
+        let head_rechannel = Conv1x1::load(cfg.channels, cfg.head_size, cfg.head_bias, weights)?;
+        Ok(Self {
+            rechannel,
+            layers,
+            head_rechannel,
+            z: vec![0.0; cfg.channels],
+            head: vec![0.0; cfg.channels],
+            head_out: vec![0.0; cfg.head_size],
+        })
+    }
+}
+
+/// Neural Amp Modeler's WaveNet: stacks of dilated convolution layers whose
+/// activations are summed into a head, per array, and chained array to array.
+#[derive(Clone)]
+pub struct WaveNet {
+    arrays: Vec<LayerArray>,
+    head_scale: f32,
+}
+
+impl WaveNet {
+    pub fn load(config: &WaveNetConfig, weights: &mut Weights) -> Result<Self, String> {
+        if config.head.is_some() {
+            return Err("WaveNet post-head is not supported".to_string());
+        }
+        if config.layers.is_empty() {
+            return Err("WaveNet without layers".to_string());
+        }
+        for (i, cfg) in config.layers.iter().enumerate() {
+            let expect_input = if i == 0 {
+                1
+            } else {
+                config.layers[i - 1].channels
+            };
+            let expect_head = match config.layers.get(i + 1) {
+                Some(next) => next.channels,
+                None => 1,
+            };
+            if cfg.input_size != expect_input
+                || cfg.condition_size != 1
+                || cfg.head_size != expect_head
+            {
+                return Err("inconsistent WaveNet layer array sizes".to_string());
+            }
+        }
+
+        let arrays = config
+            .layers
+            .iter()
+            .map(|cfg| LayerArray::load(cfg, weights))
+            .collect::<Result<Vec<_>, _>>()?;
+        // The config repeats head_scale; the copy at the end of the weights
+        // is the one NAM itself uses
+        let head_scale = weights.take(1)?[0];
+
+        Ok(Self { arrays, head_scale })
+    }
+
+    pub fn reset(&mut self) {
+        for array in self.arrays.iter_mut() {
+            for layer in array.layers.iter_mut() {
+                layer.reset();
+            }
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let condition = [x];
+        for i in 0..self.arrays.len() {
+            let (done, rest) = self.arrays.split_at_mut(i);
+            let array = &mut rest[0];
+
+            match done.last() {
+                None => {
+                    array.rechannel.apply(&condition, &mut array.z);
+                    array.head.fill(0.0);
+                }
+                Some(prev) => {
+                    array.rechannel.apply(&prev.z, &mut array.z);
+                    array.head.copy_from_slice(&prev.head_out);
+                }
+            }
+
+            for layer in array.layers.iter_mut() {
+                layer.process(&mut array.z, &condition, &mut array.head);
+            }
+            array.head_rechannel.apply(&array.head, &mut array.head_out);
+        }
+
+        self.head_scale * self.arrays[self.arrays.len() - 1].head_out[0]
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::filters::{OnePoleHp, OnePoleLp};
+use crate::dsp::shapers::{Adaa1, Shaper};
+use crate::dsp::simd::Sample;
+use crate::dsp::transient::EnvFollower;
+
+/// Per-model power amp settings.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PowerDef {
+    /// Drive into the output tubes.
+    pub gain: f32,
+    /// Global negative feedback amount, 0..~0.5. More = tighter, cleaner.
+    pub nfb: f32,
+    /// How much treble is pulled out of the feedback (and so boosted).
+    pub presence: f32,
+    /// How much bass is pulled out of the feedback (and so boosted).
+    pub resonance: f32,
+}
+
+/// Power amp with supply sag and a negative feedback loop.
+///
+/// Sag: the output level drives an envelope standing in for the current
+/// drawn from the supply. As it rises the stage loses gain and its operating
+/// point shifts, so hard playing compresses and grows even harmonics while
+/// soft playing (or rolled-back guitar volume) stays clean.
+///
+/// NFB: the previous output sample is fed back and subtracted from the
+/// input. Presence and resonance remove treble and bass from that feedback,
+/// which is how the real knobs boost those bands.
+pub struct PowerAmp<S: Sample = f32> {
+    sr: f32,
+    supply: EnvFollower<S>,
+    shaper: S::Lanes<Adaa1>,
+    fb_hp: OnePoleHp<S>,
+    fb_lp: OnePoleLp<S>,
+    last: S,
+}
+
+impl<S: Sample> PowerAmp<S> {
+    pub fn new(sr: f32) -> Self {
+        let mut p = Self {
+            sr,
+            supply: EnvFollower::new(),
+            shaper: S::lanes(|| Adaa1::new(Shaper::Soft)),
+            fb_hp: OnePoleHp::new(),
+            fb_lp: OnePoleLp::new(),
+            last: S::splat(0.0),
+        };
+        p.reset(sr);
+        p
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        self.supply.reset();
+        self.supply.set_times(sr, 0.005, 0.150);
+        self.shaper.as_mut().iter_mut().for_each(Adaa1::reset);
+        self.fb_hp = OnePoleHp::new();
+        self.fb_hp.set_cutoff(sr, 3500.0);
+        self.fb_lp = OnePoleLp::new();
+        self.fb_lp.set_cutoff(sr, 110.0);
+        self.last = S::splat(0.0);
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: S, sag: f32, def: &PowerDef) -> S {
+        let draw = self.supply.process(self.last);
+        let supply_gain = draw.map(|draw| 1.0 / (1.0 + 3.0 * sag * draw));
+        // Bias as an input offset keeps the shaper fixed, which ADAA needs
+        let bias = draw.map(|draw| 0.25 * sag * draw);
+
+        let fb = self.last
+            - self.fb_hp.process(self.last) * def.presence
+            - self.fb_lp.process(self.last) * def.resonance;
+
+        let u = (x * supply_gain - fb * def.nfb) * def.gain;
+        let y = (u + bias).map_lanes(&mut self.shaper, Adaa1::process);
+        self.last = y.flush_denormals();
+
+        // make up the level the feedback takes away
+        y * (1.0 + def.nfb)
+    }
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::fast_tanh;
+
+/// Waveshaper families used by the amp gain stages. All of them have unity
+/// slope around zero so stage gains stay comparable across types.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Shaper {
+    /// Symmetric soft clip. Odd harmonics only.
+    Soft,
+    /// Tube stage: soft clip biased off-centre, which adds even harmonics.
+    /// `bias` around 0.05..0.3 is musical.
+    Tube { bias: f32 },
+    /// Triode: grid conduction squashes the positive swing hard while
+    /// cutoff rounds off the negative swing slowly.
+    Triode,
+    /// Silicon diode pair to ground: firm clip with a small knee.
+    SiliconDiode,
+    /// Germanium diodes: soft, early and slightly asymmetric.
+    GermaniumDiode,
+    /// Hard clip at ±1 with a quadratic knee of half-width `knee` (0..1).
+    HardClip { knee: f32 },
+}
+
+impl Shaper {
+    #[inline]
+    pub fn apply(self, x: f32) -> f32 {
+        match self {
+            Shaper::Soft => soft(x),
+            Shaper::Tube { bias } => soft(x + bias) - soft(bias),
+            Shaper::Triode => {
+                if x >= 0.0 {
+                    soft(x)
+                } else {
+                    exp_clip(x, 0.5)
+                }
+            }
+            Shaper::SiliconDiode => knee_clip(x, 0.2),
+            Shaper::GermaniumDiode => {
+                if x >= 0.0 {
+                    exp_clip(x, 1.0)
+                } else {
+                    exp_clip(x, 1.4)
+                }
+            }
+            Shaper::HardClip { knee } => knee_clip(x, knee),
+        }
+    }
+
+    /// Antiderivative of `apply`, normalized so that it is 0 at x = 0.
+    /// Evaluated in f64 since ADAA divides differences of it.
+    #[inline]
+    pub fn antiderivative(self, x: f64) -> f64 {
+        match self {
+            Shaper::Soft => soft_ad(x),
+            Shaper::Tube { bias } => {
+                let b = bias as f64;
+                soft_ad(x + b) - soft_ad(b) - x * soft(bias) as f64
+            }
+            Shaper::Triode => {
+                if x >= 0.0 {
+                    soft_ad(x)
+                } else {
+                    exp_clip_ad(x, 0.5)
+                }
+            }
+            Shaper::SiliconDiode => knee_clip_ad(x, 0.2),
+            Shaper::GermaniumDiode => {
+                if x >= 0.0 {
+                    exp_clip_ad(x, 1.0)
+                } else {
+                    exp_clip_ad(x, 1.4)
+                }
+            }
+            Shaper::HardClip { knee } => knee_clip_ad(x, knee as f64),
+        }
+    }
+}
+
+/// First-order antiderivative anti-aliasing (ADAA) state for one shaper
+/// instance: `y = (F(x) - F(x1)) / (x - x1)`, i.e. the average of the shaper
+/// over the segment between consecutive samples. Costs half a sample of delay.
+pub struct Adaa1 {
+    shaper: Shaper,
+    x1: f64,
+    ad1: f64,
+}
+
+impl Adaa1 {
+    /// Below this input step the quotient gets ill-conditioned and we fall
+    /// back to evaluating the shaper at the midpoint.
+    const EPS: f64 = 1e-5;
+
+    pub fn new(shaper: Shaper) -> Self {
+        Self {
+            shaper,
+            x1: 0.0,
+            ad1: 0.0,
+        }
+    }
+
+    /// Consistent with every shaper since all antiderivatives are 0 at 0.
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.ad1 = 0.0;
+    }
+
+    /// Switches shapers without a click: the stored antiderivative is
+    /// recomputed, otherwise the next quotient would mix two different `F`s.
+    pub fn set_shaper(&mut self, shaper: Shaper) {
+        if shaper != self.shaper {
+            self.shaper = shaper;
+            self.ad1 = shaper.antiderivative(self.x1);
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32) -> f32 {
+        let shaper = self.shaper;
+        let x = x as f64;
+        let ad = shaper.antiderivative(x);
+        let dx = x - self.x1;
+
+        let y = if dx.abs() > Self::EPS {
+            ((ad - self.ad1) / dx) as f32
+        } else {
+            shaper.apply((0.5 * (x + self.x1)) as f32)
+        };
+
+        self.x1 = x;
+        self.ad1 = ad;
+        y
+    }
+}
+
+/// `fast_tanh` is only a good tanh inside ±3, where it reaches exactly ±1.
+#[inline]
+fn soft(x: f32) -> f32 {
+    fast_tanh(x.clamp(-3.0, 3.0))
+}
+
+/// `sign(x) * (1 - e^(-k|x|)) / k`: unity slope at zero, ceiling at `1 / k`.
+#[inline]
+fn exp_clip(x: f32, k: f32) -> f32 {
+    x.signum() * (1.0 - (-k * x.abs()).exp()) / k
+}
+
+/// Antiderivative of `soft`. Inside ±3: x²/18 + 4/3·ln(x² + 3), beyond it the
+/// shaper is flat at ±1 so the antiderivative grows linearly.
+#[inline]
+fn soft_ad(x: f64) -> f64 {
+    #[inline]
+    fn inner(x: f64) -> f64 {
+        x * x / 18.0 + 4.0 / 3.0 * (x * x + 3.0).ln()
+    }
+    let a = x.abs();
+    let zero = inner(0.0);
+    if a <= 3.0 {
+        inner(a) - zero
+    } else {
+        inner(3.0) - zero + (a - 3.0)
+    }
+}
+
+#[inline]
+fn exp_clip_ad(x: f64, k: f64) -> f64 {
+    let a = x.abs();
+    a / k - (1.0 - (-k * a).exp()) / (k * k)
+}
+
+#[inline]
+fn knee_clip(x: f32, knee: f32) -> f32 {
+    let knee = knee.clamp(1e-3, 1.0);
+    let a = x.abs();
+    let y = if a <= 1.0 - knee {
+        a
+    } else if a < 1.0 + knee {
+        let d = a - (1.0 - knee);
+        a - d * d / (4.0 * knee)
+    } else {
+        1.0
+    };
+    y.copysign(x)
+}
+
+#[inline]
+fn knee_clip_ad(x: f64, knee: f64) -> f64 {
+    let knee = knee.clamp(1e-3, 1.0);
+    let a = x.abs();
+    if a <= 1.0 - knee {
+        0.5 * a * a
+    } else if a < 1.0 + knee {
+        let d = a - (1.0 - knee);
+        0.5 * a * a - d * d * d / (12.0 * knee)
+    } else {
+        let top = 1.0 + knee;
+        0.5 * top * top - 2.0 * knee * knee / 3.0 + (a - top)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::fft::{Complex, RealFft};
+
+    const ALL: [Shaper; 6] = [
+        Shaper::Soft,
+        Shaper::Tube { bias: 0.15 },
+        Shaper::Triode,
+        Shaper::SiliconDiode,
+        Shaper::GermaniumDiode,
+        Shaper::HardClip { knee: 0.4 },
+    ];
+
+    const N: usize = 8192;
+    /// Sine frequency in FFT bins (~5 kHz at 48 kHz). Prime, so folded
+    /// harmonics never land on the harmonics below Nyquist.
+    const K0: usize = 853;
+
+    /// Energy outside DC and the harmonics below Nyquist, relative to the
+    /// total, for a loud sine through `shape`.
+    fn alias_ratio(mut shape: impl FnMut(f32) -> f32) -> f64 {
+        let sine =
+            |i: usize| 4.0 * (2.0 * core::f32::consts::PI * (K0 * (i % N)) as f32 / N as f32).sin();
+        // The sine is periodic in N, so after one period of settling so is the output
+        for i in 0..N {
+            shape(sine(i));
+        }
+        let y: Vec<f32> = (0..N).map(|i| shape(sine(i))).collect();
+
+        let fft = RealFft::new(N);
+        let mut spectrum = vec![Complex::ZERO; N / 2 + 1];
+        let mut scratch = vec![Complex::ZERO; N / 2];
+        fft.forward(&y, &mut spectrum, &mut scratch);
+
+        let (mut alias, mut total) = (0.0, 0.0);
+        for (k, bin) in spectrum.iter().enumerate() {
+            let e = (bin.norm() as f64).powi(2);
+            total += e;
+            if k % K0 != 0 {
+                alias += e;
+            }
+        }
+        alias / total
+    }
+
+    #[test]
+    fn adaa_aliases_less_than_naive() {
+        for shaper in ALL {
+            let naive = alias_ratio(|x| shaper.apply(x));
+            let mut adaa = Adaa1::new(shaper);
+            let antialiased = alias_ratio(|x| adaa.process(x));
+            assert!(
+                antialiased < 0.5 * naive,
+                "{shaper:?}: ADAA {antialiased:e} vs naive {naive:e}"
+            );
+        }
+    }
+
+    #[test]
+    fn switching_shapers_does_not_spike() {
+        for from in ALL {
+            for to in ALL {
+                let mut adaa = Adaa1::new(from);
+                let mut x = 0.5;
+                for _ in 0..16 {
+                    adaa.process(x);
+                    x += 0.01;
+                }
+                adaa.set_shaper(to);
+                let y = adaa.process(x);
+                let expected = to.apply(x - 0.005);
+                assert!(
+                    (y - expected).abs() < 0.01,
+                    "{from:?} -> {to:?}: {y} vs {expected}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::dsp::fast_tanh;
+use crate::dsp::filters::OnePoleLp;
+use crate::dsp::flush_denormals;
+use crate::values::ParamValues;
+use core::f32::consts::PI;
+
+/// FDN line lengths in ms, mutually prime-ish to avoid metallic ringing.
+/// Ascending.
+const LINE_MS: [f32; 4] = [29.7, 37.1, 41.1, 43.7];
+/// Grain window of the pitch shifter.
+const SHIFT_WINDOW_S: f32 = 0.05;
+/// Longest a sample can stay in the loop before it is heard: a trip
+/// through the pitch shifter, then through the longest line.
+pub const MAX_LOOP_DELAY_S: f32 = SHIFT_WINDOW_S + LINE_MS[3] * 0.001;
+
+struct Line {
+    buf: Vec<f32>,
+    idx: usize,
+}
+
+impl Line {
+    fn new(len: usize) -> Self {
+        Self {
+            buf: vec![0.0; len.max(1)],
+            idx: 0,
+        }
+    }
+
+    #[inline]
+    fn read(&self) -> f32 {
+        self.buf[self.idx]
+    }
+
+    #[inline]
+    fn write(&mut self, x: f32) {
+        self.buf[self.idx] = flush_denormals(x);
+        self.idx += 1;
+        if self.idx >= self.buf.len() {
+            self.idx = 0;
+        }
+    }
+}
+
+/// Classic two-tap delay-line pitch shifter. Two read heads sweep through a
+/// short window half a cycle apart; sin² windows sum to one, so the output
+/// never exceeds the input peak.
+struct PitchShifter {
+    buf: Vec<f32>,
+    idx: usize,
+    phase: f32,
+}
+
+impl PitchShifter {
+    fn new(len: usize) -> Self {
+        Self {
+            buf: vec![0.0; len.max(4)],
+            idx: 0,
+            phase: 0.0,
+        }
+    }
+
+    #[inline]
+    fn process(&mut self, x: f32, ratio: f32) -> f32 {
+        let len = self.buf.len();
+        self.idx += 1;
+        if self.idx >= len {
+            self.idx = 0;
+        }
+        self.buf[self.idx] = x;
+
+        let window = (len - 2) as f32;
+        self.phase = (self.phase + (ratio - 1.0) / window).rem_euclid(1.0);
+
+        let mut out = 0.0;
+        for k in 0..2 {
+            let ph = (self.phase + 0.5 * k as f32).fract();
+            // delay shrinks as phase grows -> heads run faster than the writer
+            let delay = 1.0 + (1.0 - ph) * window;
+            let pos = self.idx as f32 + len as f32 - delay;
+            let i0 = pos.floor();
+            let frac = pos - i0;
+            let i0 = i0 as usize % len;
+            let i1 = (i0 + 1) % len;
+            let s = self.buf[i0] + (self.buf[i1] - self.buf[i0]) * frac;
+            let w = (PI * ph).sin();
+            out += w * w * s;
+        }
+        out
+    }
+}
+
+/// Shimmer reverb: a 4-line Householder FDN with a pitch shifter in the
+/// feedback path, so every trip round the loop climbs by the chosen interval.
+///
+/// The loop stays bounded at every setting: the Householder matrix is
+/// orthogonal, the pitched share is blended (not added) in, the damping
+/// one-poles never boost and the loop gain tops out below one.
+pub struct Shimmer {
+    sr: f32,
+    lines: [Line; 4],
+    damp: [OnePoleLp; 4],
+    shifter: PitchShifter,
+}
+
+impl Shimmer {
+    pub fn new(sr: f32) -> Self {
+        let mut s = Self {
+            sr,
+            lines: core::array::from_fn(|_| Line::new(1)),
+            damp: core::array::from_fn(|_| OnePoleLp::new()),
+            shifter: PitchShifter::new(4),
+        };
+        s.initialize(sr);
+        s
+    }
+
+    /// Allocates the FDN and shifter buffers. Must not be called from the audio thread.
+    pub fn initialize(&mut self, sr: f32) {
+        self.lines = core::array::from_fn(|i| Line::new((LINE_MS[i] * 0.001 * sr) as usize));
+        self.shifter = PitchShifter::new((SHIFT_WINDOW_S * sr) as usize + 2);
+        self.reset(sr);
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        for line in self.lines.iter_mut() {
+            line.buf.fill(0.0);
+            line.idx = 0;
+        }
+        self.shifter.buf.fill(0.0);
+        self.shifter.idx = 0;
+        self.shifter.phase = 0.0;
+        self.damp = core::array::from_fn(|_| OnePoleLp::new());
+    }
+
+    /// Called once per processing block.
+    pub fn update_params(&mut self, sr: f32, p: &ParamValues) {
+        self.sr = sr;
+        let hz = p.shimmer_highcut_hz;
+        for lp in self.damp.iter_mut() {
+            lp.set_cutoff(self.sr, hz);
+        }
+    }
+
+    /// Returns the wet (reverb only) signal.
+    #[inline]
+    pub fn process(&mut self, l: f32, r: f32, p: &ParamValues) -> (f32, f32) {
+        let amount = p.shimmer_amount.clamp(0.0, 1.0);
+        let g = 0.5 + 0.45 * p.shimmer_decay.clamp(0.0, 1.0);
+        let ratio = (p.shimmer_interval.semitones() / 12.0).exp2();
+
+        let o: [f32; 4] = core::array::from_fn(|i| self.lines[i].read());
+
+        // Householder mix: m = o - (2/N) * sum(o)
+        let half_sum = 0.5 * (o[0] + o[1] + o[2] + o[3]);
+        let m = o.map(|v| v - half_sum);
+
+        // Pitch the mean of the mix; a constant vector of it has at most the norm of `m`
+        let mean = 0.25 * (m[0] + m[1] + m[2] + m[3]);
+        let shifted = self.shifter.process(mean, ratio);
+
+        let input = 0.25 * (l + r);
+        for ((line, damp), m) in self.lines.iter_mut().zip(&mut self.damp).zip(m) {
+            let fb = g * ((1.0 - amount) * m + amount * shifted);
+            let fb = fast_tanh(damp.process(fb));
+            line.write(input + fb);
+        }
+
+        let wet_l = 0.7 * (o[0] + o[2]);
+        let wet_r = 0.7 * (o[1] + o[3]);
+        (flush_denormals(wet_l), flush_denormals(wet_r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::rng::XorShift32;
+    use crate::params::{GtrParams, ShimmerInterval};
+    use nih_plug::prelude::Enum;
+
+    #[test]
+    fn feedback_stays_bounded_at_full_decay_and_amount() {
+        let sr = 48000.0;
+        let mut v = ParamValues::from_params(&GtrParams::default());
+        v.shimmer_decay = 1.0;
+        v.shimmer_amount = 1.0;
+
+        for index in 0..ShimmerInterval::variants().len() {
+            for highcut in [1000.0, 16000.0] {
+                v.shimmer_interval = ShimmerInterval::from_index(index);
+                v.shimmer_highcut_hz = highcut;
+                let mut shimmer = Shimmer::new(sr);
+                shimmer.update_params(sr, &v);
+
+                let mut rng = XorShift32::new(7);
+                let mut peak = 0.0f32;
+                for i in 0..(4.0 * sr) as usize {
+                    // an impulse, then full-scale noise
+                    let (l, r) = if i == 0 {
+                        (1.0, 1.0)
+                    } else {
+                        (2.0 * rng.next_f32() - 1.0, 2.0 * rng.next_f32() - 1.0)
+                    };
+                    let (wl, wr) = shimmer.process(l, r, &v);
+                    assert!(wl.is_finite() && wr.is_finite());
+                    peak = peak.max(wl.abs()).max(wr.abs());
+                }
+                let name = ShimmerInterval::variants()[index];
+                assert!(peak < 2.2, "{name} at {highcut} Hz: peak {peak}");
+            }
+        }
+    }
+}
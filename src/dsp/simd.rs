@@ -0,0 +1,185 @@
+//! Sample types the guitar chain can run on: plain `f32` for one channel, or
+//! `F32x2` for both stereo channels in the lanes of one vector.
+//!
+//! Every lane goes through exactly the operations the scalar path does, in
+//! the same order, so a lane of `F32x2` is bit-identical to running `f32`.
+//! Lane-dependent branches become selects. Work that only makes sense one
+//! lane at a time (ADAA shapers, NAM models, the f64 tone stack) keeps
+//! per-lane state in `Sample::Lanes` and runs through `map_lanes`.
+//!
+//! `F32x2` is plain `[f32; 2]` arithmetic left to the autovectorizer, so
+//! how much it gains depends on the optimization level: check with the
+//! ignored `stereo_lanes_speed` test in `chain.rs` before relying on it.
+
+use core::ops::{Add, AddAssign, Mul, Sub};
+
+use crate::dsp::flush_denormals;
+
+pub trait Sample:
+    Copy
+    + PartialEq
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Mul<f32, Output = Self>
+    + AddAssign
+{
+    /// Per-lane storage for state that can't be vectorized.
+    type Lanes<T>: AsRef<[T]> + AsMut<[T]>;
+
+    fn splat(x: f32) -> Self;
+
+    fn lanes<T>(f: impl FnMut() -> T) -> Self::Lanes<T>;
+
+    /// Applies `f` to every lane.
+    fn map(self, f: impl FnMut(f32) -> f32) -> Self;
+
+    /// Applies `f` to matching lanes of `self` and `other`.
+    fn zip_map(self, other: Self, f: impl FnMut(f32, f32) -> f32) -> Self;
+
+    /// Applies `f` to every lane together with that lane's state.
+    fn map_lanes<T>(self, state: &mut Self::Lanes<T>, f: impl FnMut(&mut T, f32) -> f32) -> Self;
+
+    /// Per lane: `if a > b { t } else { f }`.
+    fn select_gt(a: Self, b: Self, t: Self, f: Self) -> Self;
+
+    #[inline]
+    fn abs(self) -> Self {
+        self.map(f32::abs)
+    }
+
+    #[inline]
+    fn flush_denormals(self) -> Self {
+        self.map(flush_denormals)
+    }
+}
+
+impl Sample for f32 {
+    type Lanes<T> = [T; 1];
+
+    #[inline]
+    fn splat(x: f32) -> Self {
+        x
+    }
+
+    fn lanes<T>(mut f: impl FnMut() -> T) -> [T; 1] {
+        [f()]
+    }
+
+    #[inline]
+    fn map(self, mut f: impl FnMut(f32) -> f32) -> Self {
+        f(self)
+    }
+
+    #[inline]
+    fn zip_map(self, other: Self, mut f: impl FnMut(f32, f32) -> f32) -> Self {
+        f(self, other)
+    }
+
+    #[inline]
+    fn map_lanes<T>(self, state: &mut [T; 1], mut f: impl FnMut(&mut T, f32) -> f32) -> Self {
+        f(&mut state[0], self)
+    }
+
+    #[inline]
+    fn select_gt(a: Self, b: Self, t: Self, f: Self) -> Self {
+        if a > b {
+            t
+        } else {
+            f
+        }
+    }
+}
+
+/// Left and right channel in two lanes. Plain array arithmetic, which the
+/// compiler turns into SSE/NEON on its own.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[repr(C, align(8))]
+pub struct F32x2(pub [f32; 2]);
+
+impl F32x2 {
+    #[inline]
+    pub fn new(l: f32, r: f32) -> Self {
+        Self([l, r])
+    }
+}
+
+impl Add for F32x2 {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Self([self.0[0] + rhs.0[0], self.0[1] + rhs.0[1]])
+    }
+}
+
+impl AddAssign for F32x2 {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for F32x2 {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Self([self.0[0] - rhs.0[0], self.0[1] - rhs.0[1]])
+    }
+}
+
+impl Mul for F32x2 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        Self([self.0[0] * rhs.0[0], self.0[1] * rhs.0[1]])
+    }
+}
+
+impl Mul<f32> for F32x2 {
+    type Output = Self;
+
+    #[inline]
+    fn mul(self, rhs: f32) -> Self {
+        Self([self.0[0] * rhs, self.0[1] * rhs])
+    }
+}
+
+impl Sample for F32x2 {
+    type Lanes<T> = [T; 2];
+
+    #[inline]
+    fn splat(x: f32) -> Self {
+        Self([x; 2])
+    }
+
+    fn lanes<T>(mut f: impl FnMut() -> T) -> [T; 2] {
+        [f(), f()]
+    }
+
+    #[inline]
+    fn map(self, f: impl FnMut(f32) -> f32) -> Self {
+        Self(self.0.map(f))
+    }
+
+    #[inline]
+    fn zip_map(self, other: Self, mut f: impl FnMut(f32, f32) -> f32) -> Self {
+        Self([f(self.0[0], other.0[0]), f(self.0[1], other.0[1])])
+    }
+
+    #[inline]
+    fn map_lanes<T>(self, state: &mut [T; 2], mut f: impl FnMut(&mut T, f32) -> f32) -> Self {
+        let [a, b] = state;
+        Self([f(a, self.0[0]), f(b, self.0[1])])
+    }
+
+    #[inline]
+    fn select_gt(a: Self, b: Self, t: Self, f: Self) -> Self {
+        Self([
+            if a.0[0] > b.0[0] { t.0[0] } else { f.0[0] },
+            if a.0[1] > b.0[1] { t.0[1] } else { f.0[1] },
+        ])
+    }
+}
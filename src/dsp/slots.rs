@@ -0,0 +1,70 @@
+/// Fade out / fade in time used when the slot order changes.
+pub const SWAP_FADE_S: f32 = 0.005;
+
+/// Turns a list of slot choices into a processing order that contains every
+/// module exactly once: repeated picks are dropped and whatever wasn't picked
+/// is appended in `default` order.
+pub fn resolve_order<M: Copy + PartialEq, const N: usize>(
+    slots: [M; N],
+    default: [M; N],
+) -> [M; N] {
+    let mut order = default;
+    let mut len = 0;
+    for m in slots.into_iter().chain(default) {
+        if !order[..len].contains(&m) {
+            order[len] = m;
+            len += 1;
+        }
+    }
+    order
+}
+
+/// Module order for a chain of slots, changed click-free: the output is
+/// faded out, the new order swapped in at silence, then faded back in.
+pub struct SlotOrder<M, const N: usize> {
+    current: [M; N],
+    target: [M; N],
+    gain: f32,
+    step: f32,
+}
+
+impl<M: Copy + PartialEq, const N: usize> SlotOrder<M, N> {
+    pub fn new(sr: f32, order: [M; N]) -> Self {
+        let mut s = Self {
+            current: order,
+            target: order,
+            gain: 1.0,
+            step: 1.0,
+        };
+        s.reset(sr);
+        s
+    }
+
+    /// Jumps straight to the target order.
+    pub fn reset(&mut self, sr: f32) {
+        self.current = self.target;
+        self.gain = 1.0;
+        self.step = 1.0 / (SWAP_FADE_S * sr).max(1.0);
+    }
+
+    /// Called once per block with the order the params ask for.
+    pub fn set_target(&mut self, order: [M; N]) {
+        self.target = order;
+    }
+
+    /// Advances the swap fade by one sample. Returns the order to run and the
+    /// gain to apply to its output.
+    #[inline]
+    pub fn next(&mut self) -> ([M; N], f32) {
+        if self.current != self.target {
+            self.gain -= self.step;
+            if self.gain <= 0.0 {
+                self.gain = 0.0;
+                self.current = self.target;
+            }
+        } else if self.gain < 1.0 {
+            self.gain = (self.gain + self.step).min(1.0);
+        }
+        (self.current, self.gain)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::dsp::fft::{Complex, RealFft};
+use core::f32::consts::PI;
+
+/// Streaming STFT analysis/resynthesis with a periodic Hann window on both
+/// ends and 75% overlap. Feed one sample at a time; every hop the callback
+/// gets the frame's spectrum to inspect or rewrite in place.
+///
+/// Output lags input by `size` samples. All buffers are allocated in
+/// `new`.
+pub struct Stft {
+    size: usize,
+    hop: usize,
+    fft: RealFft,
+    window: Vec<f32>,
+    input: Vec<f32>,
+    accum: Vec<f32>,
+    output: Vec<f32>,
+    frame: Vec<f32>,
+    spectrum: Vec<Complex>,
+    scratch: Vec<Complex>,
+    count: usize,
+    norm: f32,
+}
+
+impl Stft {
+    /// `size` must be a power of two.
+    pub fn new(size: usize) -> Self {
+        let hop = size / 4;
+        let window = (0..size)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos())
+            .collect();
+        Self {
+            size,
+            hop,
+            fft: RealFft::new(size),
+            window,
+            input: vec![0.0; size],
+            accum: vec![0.0; size],
+            output: vec![0.0; hop],
+            frame: vec![0.0; size],
+            spectrum: vec![Complex::ZERO; size / 2 + 1],
+            scratch: vec![Complex::ZERO; size / 2],
+            count: size - hop,
+            // Hann² at 75% overlap sums to 1.5
+            norm: 1.0 / 1.5,
+        }
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    pub fn reset(&mut self) {
+        self.input.fill(0.0);
+        self.accum.fill(0.0);
+        self.output.fill(0.0);
+        self.count = self.size - self.hop;
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: f32, mut f: impl FnMut(&mut [Complex])) -> f32 {
+        self.input[self.count] = x;
+        let y = self.output[self.count - (self.size - self.hop)];
+        self.count += 1;
+
+        if self.count == self.size {
+            for ((o, i), w) in self.frame.iter_mut().zip(&self.input).zip(&self.window) {
+                *o = i * w;
+            }
+            self.fft
+                .forward(&self.frame, &mut self.spectrum, &mut self.scratch);
+
+            f(&mut self.spectrum);
+
+            self.fft
+                .inverse(&self.spectrum, &mut self.frame, &mut self.scratch);
+            for ((a, s), w) in self.accum.iter_mut().zip(&self.frame).zip(&self.window) {
+                *a += s * w * self.norm;
+            }
+
+            // Hand out the finished hop and slide everything along
+            self.output.copy_from_slice(&self.accum[..self.hop]);
+            self.accum.copy_within(self.hop.., 0);
+            let tail = self.size - self.hop;
+            self.accum[tail..].fill(0.0);
+            self.input.copy_within(self.hop.., 0);
+            self.count = tail;
+        }
+
+        y
+    }
+}
@@ -0,0 +1,182 @@
+use crate::dsp::{flush_denormals, time_to_coeff};
+use crate::values::ParamValues;
+
+/// Longest slice we can capture: a quarter note at 30 BPM.
+const MAX_SLICE_S: f32 = 2.0;
+/// Fade at the slice edges so the loop point doesn't click.
+const EDGE_FADE_S: f32 = 0.002;
+/// Cap on the pitch shift accumulated over the repeats, in semitones.
+const MAX_SHIFT_ST: f32 = 24.0;
+/// Tempo used when the host doesn't report one.
+const FALLBACK_BPM: f64 = 120.0;
+
+enum State {
+    /// Trigger released, audio passes through.
+    Idle,
+    /// Trigger held, waiting for the next grid line.
+    Armed,
+    /// Recording the slice (output is still the live signal).
+    Capturing { pos: usize },
+    /// Looping the captured slice. `index` is 1 for the first repeat.
+    Repeating { read: f32, index: u32 },
+}
+
+/// Tempo-synced beat repeat: while the trigger is held, grabs one grid
+/// division of audio at the next host grid line and loops it, optionally
+/// pitching and fading every repeat.
+pub struct BeatRepeat {
+    sr: f32,
+    buf_l: Vec<f32>,
+    buf_r: Vec<f32>,
+    slice_len: usize,
+    state: State,
+    beat_pos: f64,
+    beats_per_sample: f64,
+    last_cell: i64,
+    wet: f32,
+    wet_coeff: f32,
+}
+
+impl BeatRepeat {
+    pub fn new(sr: f32) -> Self {
+        let mut b = Self {
+            sr,
+            buf_l: Vec::new(),
+            buf_r: Vec::new(),
+            slice_len: 1,
+            state: State::Idle,
+            beat_pos: 0.0,
+            beats_per_sample: 0.0,
+            last_cell: 0,
+            wet: 0.0,
+            wet_coeff: 0.0,
+        };
+        b.initialize(sr);
+        b
+    }
+
+    /// Allocates the capture buffers. Must not be called from the audio thread.
+    pub fn initialize(&mut self, sr: f32) {
+        let len = ((MAX_SLICE_S * sr) as usize).max(1);
+        self.buf_l = vec![0.0; len];
+        self.buf_r = vec![0.0; len];
+        self.reset(sr);
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        self.buf_l.fill(0.0);
+        self.buf_r.fill(0.0);
+        self.slice_len = 1;
+        self.state = State::Idle;
+        self.beat_pos = 0.0;
+        self.beats_per_sample = FALLBACK_BPM / 60.0 / sr as f64;
+        self.last_cell = 0;
+        self.wet = 0.0;
+        self.wet_coeff = time_to_coeff(0.005, sr);
+    }
+
+    /// Called once per block with the host transport. Without a playing
+    /// transport the grid free-runs from the last known position.
+    pub fn set_transport(&mut self, tempo: Option<f64>, pos_beats: Option<f64>, playing: bool) {
+        let bpm = tempo.filter(|t| *t > 0.0).unwrap_or(FALLBACK_BPM);
+        self.beats_per_sample = bpm / 60.0 / self.sr as f64;
+        if playing {
+            if let Some(pos) = pos_beats {
+                self.beat_pos = pos;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn process(&mut self, l: f32, r: f32, p: &ParamValues) -> (f32, f32) {
+        let held = p.stutter_on;
+        let div = p.stutter_div.beats();
+
+        // Grid crossing detection
+        let cell = (self.beat_pos / div).floor() as i64;
+        let on_grid = cell != self.last_cell;
+        self.last_cell = cell;
+        self.beat_pos += self.beats_per_sample;
+
+        match self.state {
+            State::Idle if held => self.state = State::Armed,
+            State::Armed if !held => self.state = State::Idle,
+            // let a running repeat fade out before dropping it
+            State::Capturing { .. } | State::Repeating { .. } if !held && self.wet < 1e-3 => {
+                self.state = State::Idle
+            }
+            _ => {}
+        }
+
+        if on_grid && matches!(self.state, State::Armed) {
+            let len = (div / self.beats_per_sample) as usize;
+            self.slice_len = len.clamp(1, self.buf_l.len());
+            self.state = State::Capturing { pos: 0 };
+        }
+
+        let mut out_l = l;
+        let mut out_r = r;
+        let mut repeating = false;
+        let mut next = None;
+
+        match &mut self.state {
+            State::Idle | State::Armed => {}
+            State::Capturing { pos } => {
+                self.buf_l[*pos] = l;
+                self.buf_r[*pos] = r;
+                *pos += 1;
+                if *pos >= self.slice_len {
+                    next = Some(State::Repeating {
+                        read: 0.0,
+                        index: 1,
+                    });
+                }
+            }
+            State::Repeating { read, index } => {
+                let len = self.slice_len as f32;
+                // the shift adds up per repeat; unbounded, the read head
+                // would all but stall on long downward runs
+                let shift = (p.stutter_pitch * *index as f32).clamp(-MAX_SHIFT_ST, MAX_SHIFT_ST);
+                let ratio = (shift / 12.0).exp2();
+                let gain = (1.0 - p.stutter_decay).powi(*index as i32);
+
+                // Linear interpolation for pitched playback
+                let i0 = (*read as usize).min(self.slice_len - 1);
+                let i1 = (i0 + 1).min(self.slice_len - 1);
+                let frac = *read - i0 as f32;
+                let rl = self.buf_l[i0] + (self.buf_l[i1] - self.buf_l[i0]) * frac;
+                let rr = self.buf_r[i0] + (self.buf_r[i1] - self.buf_r[i0]) * frac;
+
+                let fade_len = (EDGE_FADE_S * self.sr).max(1.0);
+                let fade = (read.min(len - *read) / fade_len).clamp(0.0, 1.0);
+
+                out_l = rl * gain * fade;
+                out_r = rr * gain * fade;
+                repeating = true;
+
+                *read += ratio;
+                if *read >= len {
+                    *read = 0.0;
+                    *index += 1;
+                    if *index > p.stutter_repeats as u32 {
+                        // grab a fresh slice at the next grid line
+                        next = Some(State::Armed);
+                    }
+                }
+            }
+        }
+
+        if let Some(s) = next {
+            self.state = s;
+        }
+
+        // Smooth engage/release between live and repeated audio
+        let target = if repeating && held { 1.0 } else { 0.0 };
+        self.wet = target + (self.wet - target) * self.wet_coeff;
+
+        let l = l + (out_l - l) * self.wet;
+        let r = r + (out_r - r) * self.wet;
+        (flush_denormals(l), flush_denormals(r))
+    }
+}
@@ -0,0 +1,304 @@
+use crate::dsp::flush_denormals;
+use crate::params::TapeStopMode;
+use crate::values::ParamValues;
+
+/// Longest lag the read head may fall behind the write head.
+const MAX_LAG_S: f32 = 6.0;
+/// Half-time plays this much source audio per segment before jumping back to live.
+const HALF_TIME_SEGMENT_S: f32 = 0.25;
+/// Crossfade used for every read head jump.
+const JUMP_FADE_S: f32 = 0.01;
+
+#[derive(PartialEq, Clone, Copy)]
+enum Phase {
+    Live,
+    Slowing,
+    Held,
+    SpinUp,
+    Resync,
+}
+
+/// Varispeed tape reader for tape-stop ("powerdown") and half-time drops.
+///
+/// Input is always written to a ring buffer; the read head runs at `speed`
+/// and trails the write head by `lag` samples. Everything is driven by the
+/// sample counter, so offline renders match realtime ones exactly.
+pub struct TapeStop {
+    sr: f32,
+    buf_l: Vec<f32>,
+    buf_r: Vec<f32>,
+    write: usize,
+    phase: Phase,
+    speed: f32,
+    ramp_from: f32,
+    ramp_to: f32,
+    ramp_pos: u32,
+    ramp_len: u32,
+    /// Lag of the main read head.
+    lag: f64,
+    /// Lag of the head being faded out after a jump.
+    lag_old: f64,
+    fade_pos: u32,
+    fade_len: u32,
+}
+
+impl TapeStop {
+    pub fn new(sr: f32) -> Self {
+        let mut t = Self {
+            sr,
+            buf_l: Vec::new(),
+            buf_r: Vec::new(),
+            write: 0,
+            phase: Phase::Live,
+            speed: 1.0,
+            ramp_from: 1.0,
+            ramp_to: 1.0,
+            ramp_pos: 0,
+            ramp_len: 1,
+            lag: 0.0,
+            lag_old: 0.0,
+            fade_pos: 0,
+            fade_len: 1,
+        };
+        t.initialize(sr);
+        t
+    }
+
+    /// Allocates the tape buffers. Must not be called from the audio thread.
+    pub fn initialize(&mut self, sr: f32) {
+        let len = ((MAX_LAG_S * sr) as usize).max(4);
+        self.buf_l = vec![0.0; len];
+        self.buf_r = vec![0.0; len];
+        self.reset(sr);
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        self.buf_l.fill(0.0);
+        self.buf_r.fill(0.0);
+        self.write = 0;
+        self.phase = Phase::Live;
+        self.speed = 1.0;
+        self.ramp_from = 1.0;
+        self.ramp_to = 1.0;
+        self.ramp_pos = 0;
+        self.ramp_len = 1;
+        self.lag = 0.0;
+        self.lag_old = 0.0;
+        self.fade_len = ((JUMP_FADE_S * sr) as u32).max(1);
+        self.fade_pos = self.fade_len;
+    }
+
+    fn start_ramp(&mut self, to: f32, time_ms: f32) {
+        self.ramp_from = self.speed;
+        self.ramp_to = to;
+        self.ramp_pos = 0;
+        self.ramp_len = ((time_ms * 0.001 * self.sr) as u32).max(1);
+    }
+
+    /// Crossfades the read head over to a new lag.
+    fn jump_to(&mut self, lag: f64) {
+        self.lag_old = self.lag;
+        self.lag = lag;
+        self.fade_pos = 0;
+    }
+
+    #[inline]
+    fn read(&self, lag: f64) -> (f32, f32) {
+        let len = self.buf_l.len();
+        let pos = self.write as f64 + len as f64 - lag;
+        let i0 = pos.floor();
+        let frac = (pos - i0) as f32;
+        let i0 = i0 as usize % len;
+        let i1 = (i0 + 1) % len;
+        let l = self.buf_l[i0] + (self.buf_l[i1] - self.buf_l[i0]) * frac;
+        let r = self.buf_r[i0] + (self.buf_r[i1] - self.buf_r[i0]) * frac;
+        (l, r)
+    }
+
+    #[inline]
+    pub fn process(&mut self, l: f32, r: f32, p: &ParamValues) -> (f32, f32) {
+        let held = p.tapestop_on;
+        let mode = p.tapestop_mode;
+
+        // Trigger edges
+        match (held, self.phase) {
+            (true, Phase::Live | Phase::SpinUp | Phase::Resync) => {
+                let target = match mode {
+                    TapeStopMode::Stop => 0.0,
+                    TapeStopMode::HalfTime => 0.5,
+                };
+                self.start_ramp(target, p.tapestop_time_ms);
+                self.phase = Phase::Slowing;
+            }
+            (false, Phase::Slowing | Phase::Held) => {
+                self.start_ramp(1.0, p.tapestop_spinup_ms);
+                self.phase = Phase::SpinUp;
+            }
+            _ => {}
+        }
+
+        if self.phase == Phase::Live {
+            self.write_sample(l, r);
+            return (l, r);
+        }
+
+        // Speed ramp, shaped by the curve param (0 = linear, 1 = fast drop, long tail)
+        if self.ramp_pos < self.ramp_len {
+            self.ramp_pos += 1;
+            let t = self.ramp_pos as f32 / self.ramp_len as f32;
+            let k = 1.0 + 3.0 * p.tapestop_curve;
+            let shaped = 1.0 - (1.0 - t).powf(k);
+            self.speed = self.ramp_from + (self.ramp_to - self.ramp_from) * shaped;
+        } else {
+            match self.phase {
+                Phase::Slowing => self.phase = Phase::Held,
+                Phase::SpinUp => {
+                    self.phase = Phase::Resync;
+                    self.jump_to(0.0);
+                }
+                _ => {}
+            }
+        }
+
+        self.write_sample(l, r);
+
+        let max_lag = (self.buf_l.len() - 2) as f64;
+        let drift = 1.0 - self.speed as f64;
+        self.lag = (self.lag + drift).clamp(0.0, max_lag);
+        self.lag_old = (self.lag_old + drift).clamp(0.0, max_lag);
+
+        // Half-time: once a segment has been stretched out, hop back to live
+        if mode == TapeStopMode::HalfTime
+            && matches!(self.phase, Phase::Slowing | Phase::Held)
+            && self.fade_pos >= self.fade_len
+            && self.lag >= (HALF_TIME_SEGMENT_S * self.sr) as f64
+        {
+            self.jump_to(0.0);
+        }
+
+        let (mut out_l, mut out_r) = self.read(self.lag);
+        if self.fade_pos < self.fade_len {
+            let x = self.fade_pos as f32 / self.fade_len as f32;
+            let (old_l, old_r) = self.read(self.lag_old);
+            out_l = old_l + (out_l - old_l) * x;
+            out_r = old_r + (out_r - old_r) * x;
+            self.fade_pos += 1;
+        } else if self.phase == Phase::Resync {
+            self.phase = Phase::Live;
+            self.speed = 1.0;
+            self.lag = 0.0;
+        }
+
+        // Fade out as the tape grinds to a halt instead of freezing on DC
+        let gain = (self.speed * 8.0).min(1.0);
+        (flush_denormals(out_l * gain), flush_denormals(out_r * gain))
+    }
+
+    #[inline]
+    fn write_sample(&mut self, l: f32, r: f32) {
+        self.write += 1;
+        if self.write >= self.buf_l.len() {
+            self.write = 0;
+        }
+        self.buf_l[self.write] = l;
+        self.buf_r[self.write] = r;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::GtrParams;
+
+    const SR: f32 = 48000.0;
+
+    fn values() -> ParamValues {
+        let mut v = ParamValues::from_params(&GtrParams::default());
+        v.tapestop_mode = TapeStopMode::Stop;
+        v.tapestop_time_ms = 500.0;
+        v.tapestop_spinup_ms = 300.0;
+        v
+    }
+
+    fn input(i: usize) -> (f32, f32) {
+        let t = i as f32 / SR;
+        let l = 0.5 * (std::f32::consts::TAU * 220.0 * t).sin();
+        (l, 0.5 * (std::f32::consts::TAU * 330.0 * t).sin())
+    }
+
+    /// Renders `len` samples with the trigger held over `held`, in blocks of
+    /// `block` samples that are also split at every trigger change, the way
+    /// hosts deliver sample-accurate automation.
+    fn render(len: usize, held: std::ops::Range<usize>, block: usize) -> Vec<(f32, f32)> {
+        let mut tape = TapeStop::new(SR);
+        let mut v = values();
+        let mut out = Vec::with_capacity(len);
+        let mut start = 0;
+        while start < len {
+            let mut end = (start + block).min(len);
+            for edge in [held.start, held.end] {
+                if edge > start && edge < end {
+                    end = edge;
+                }
+            }
+            v.tapestop_on = held.contains(&start);
+            for i in start..end {
+                let (l, r) = input(i);
+                out.push(tape.process(l, r, &v));
+            }
+            start = end;
+        }
+        out
+    }
+
+    fn bits(out: &[(f32, f32)]) -> Vec<(u32, u32)> {
+        out.iter()
+            .map(|(l, r)| (l.to_bits(), r.to_bits()))
+            .collect()
+    }
+
+    #[test]
+    fn renders_identically_across_runs_and_block_sizes() {
+        let len = (2.0 * SR) as usize;
+        let held = 4800..40000;
+        let first = bits(&render(len, held.clone(), 512));
+        assert_eq!(first, bits(&render(len, held.clone(), 512)));
+        assert_eq!(first, bits(&render(len, held.clone(), 64)));
+        assert_eq!(first, bits(&render(len, held, 1001)));
+    }
+
+    #[test]
+    fn release_while_slowing_spins_up_without_jumps() {
+        let mut tape = TapeStop::new(SR);
+        let mut v = values();
+        let fade_len = (JUMP_FADE_S * SR) as usize;
+        // steepest the input moves, plus a full-scale swing spread over the fade
+        let max_step = 0.5 * std::f32::consts::TAU * 330.0 / SR + 1.0 / fade_len as f32;
+
+        let mut phases = Vec::new();
+        let mut last = (0.0, 0.0);
+        for i in 0..(1.5 * SR) as usize {
+            // hold for 200 ms of the 500 ms slow-down
+            v.tapestop_on = (4800..4800 + 9600).contains(&i);
+            let (l, r) = input(i);
+            let y = tape.process(l, r, &v);
+            let step = (y.0 - last.0).abs().max((y.1 - last.1).abs());
+            assert!(step <= max_step, "jump of {step} at {i}");
+            last = y;
+            if phases.last() != Some(&tape.phase) {
+                phases.push(tape.phase);
+            }
+        }
+        assert!(
+            phases
+                == [
+                    Phase::Live,
+                    Phase::Slowing,
+                    Phase::SpinUp,
+                    Phase::Resync,
+                    Phase::Live
+                ]
+        );
+    }
+}
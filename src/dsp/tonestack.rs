@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+
+use crate::dsp::flush_denormals;
+use crate::dsp::simd::Sample;
+
+/// Which passive bass/mid/treble network to model.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToneStackType {
+    /// '59 Bassman: deep mid scoop, lots of treble range.
+    Fender,
+    /// JCM800-style: shallower scoop, mids stay forward.
+    Marshall,
+    /// Top Boost-like values: chimey highs, tight lows.
+    Vox,
+}
+
+/// Component values of the FMV (Fender/Marshall/Vox) network.
+struct Components {
+    r1: f64,
+    r2: f64,
+    r3: f64,
+    r4: f64,
+    c1: f64,
+    c2: f64,
+    c3: f64,
+}
+
+impl ToneStackType {
+    fn components(self) -> Components {
+        match self {
+            ToneStackType::Fender => Components {
+                r1: 250e3,
+                r2: 1e6,
+                r3: 25e3,
+                r4: 56e3,
+                c1: 250e-12,
+                c2: 20e-9,
+                c3: 20e-9,
+            },
+            ToneStackType::Marshall => Components {
+                r1: 220e3,
+                r2: 1e6,
+                r3: 22e3,
+                r4: 33e3,
+                c1: 470e-12,
+                c2: 22e-9,
+                c3: 22e-9,
+            },
+            ToneStackType::Vox => Components {
+                r1: 1e6,
+                r2: 1e6,
+                r3: 10e3,
+                r4: 100e3,
+                c1: 50e-12,
+                c2: 22e-9,
+                c3: 22e-9,
+            },
+        }
+    }
+
+    /// Rough make-up gain for the stack's insertion loss at noon.
+    fn makeup(self) -> f64 {
+        match self {
+            ToneStackType::Fender => 3.0,
+            ToneStackType::Marshall => 2.5,
+            ToneStackType::Vox => 3.5,
+        }
+    }
+}
+
+/// Passive tone stack, after D. Yeh's analysis of the FMV network: the
+/// third-order analog transfer function is built from the component values
+/// and pot positions, then discretized with the bilinear transform.
+pub struct ToneStack<S: Sample = f32> {
+    b: [f64; 4],
+    a: [f64; 4],
+    /// Runs in f64, one lane at a time.
+    z: S::Lanes<[f64; 3]>,
+    /// (type, bass, mid, treble, sr) the coefficients were built for.
+    current: Option<(ToneStackType, f32, f32, f32, f32)>,
+}
+
+impl<S: Sample> ToneStack<S> {
+    pub fn new() -> Self {
+        Self {
+            b: [1.0, 0.0, 0.0, 0.0],
+            a: [1.0, 0.0, 0.0, 0.0],
+            z: S::lanes(|| [0.0; 3]),
+            current: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.z.as_mut().fill([0.0; 3]);
+    }
+
+    /// Recomputes the coefficients if anything changed. Knobs are 0..1.
+    pub fn set(&mut self, sr: f32, kind: ToneStackType, bass: f32, mid: f32, treble: f32) {
+        let key = (kind, bass, mid, treble, sr);
+        if self.current == Some(key) {
+            return;
+        }
+        self.current = Some(key);
+
+        let Components {
+            r1,
+            r2,
+            r3,
+            r4,
+            c1,
+            c2,
+            c3,
+        } = kind.components();
+
+        // Bass is a log-taper pot in the real circuit
+        let l = ((bass.clamp(0.0, 1.0) as f64 - 1.0) * 3.4).exp();
+        let m = mid.clamp(0.0, 1.0) as f64;
+        let t = treble.clamp(0.0, 1.0) as f64;
+
+        let b1 = t * c1 * r1 + m * c3 * r3 + l * (c1 * r2 + c2 * r2) + (c1 * r3 + c2 * r3);
+        let b2 = t * (c1 * c2 * r1 * r4 + c1 * c3 * r1 * r4)
+            - m * m * (c1 * c3 * r3 * r3 + c2 * c3 * r3 * r3)
+            + m * (c1 * c3 * r1 * r3 + c1 * c3 * r3 * r3 + c2 * c3 * r3 * r3)
+            + l * (c1 * c2 * r1 * r2 + c1 * c2 * r2 * r4 + c1 * c3 * r2 * r4)
+            + l * m * (c1 * c3 * r2 * r3 + c2 * c3 * r2 * r3)
+            + (c1 * c2 * r1 * r3 + c1 * c2 * r3 * r4 + c1 * c3 * r3 * r4);
+        let b3 = l * m * (c1 * c2 * c3 * r1 * r2 * r3 + c1 * c2 * c3 * r2 * r3 * r4)
+            - m * m * (c1 * c2 * c3 * r1 * r3 * r3 + c1 * c2 * c3 * r3 * r3 * r4)
+            + m * (c1 * c2 * c3 * r1 * r3 * r3 + c1 * c2 * c3 * r3 * r3 * r4)
+            + t * c1 * c2 * c3 * r1 * r3 * r4
+            - t * m * c1 * c2 * c3 * r1 * r3 * r4
+            + t * l * c1 * c2 * c3 * r1 * r2 * r4;
+
+        let a1 = (c1 * r1 + c1 * r3 + c2 * r3 + c2 * r4 + c3 * r4)
+            + m * c3 * r3
+            + l * (c1 * r2 + c2 * r2);
+        let a2 = m
+            * (c1 * c3 * r1 * r3 - c2 * c3 * r3 * r4 + c1 * c3 * r3 * r3 + c2 * c3 * r3 * r3)
+            + l * m * (c1 * c3 * r2 * r3 + c2 * c3 * r2 * r3)
+            - m * m * (c1 * c3 * r3 * r3 + c2 * c3 * r3 * r3)
+            + l * (c1 * c2 * r2 * r4 + c1 * c2 * r1 * r2 + c1 * c3 * r2 * r4 + c2 * c3 * r2 * r4)
+            + (c1 * c2 * r1 * r4
+                + c1 * c3 * r1 * r4
+                + c1 * c2 * r3 * r4
+                + c1 * c2 * r1 * r3
+                + c1 * c3 * r3 * r4
+                + c2 * c3 * r3 * r4);
+        let a3 = l * m * (c1 * c2 * c3 * r1 * r2 * r3 + c1 * c2 * c3 * r2 * r3 * r4)
+            - m * m * (c1 * c2 * c3 * r1 * r3 * r3 + c1 * c2 * c3 * r3 * r3 * r4)
+            + m * (c1 * c2 * c3 * r3 * r3 * r4 + c1 * c2 * c3 * r1 * r3 * r3
+                - c1 * c2 * c3 * r1 * r3 * r4)
+            + l * c1 * c2 * c3 * r1 * r2 * r4
+            + c1 * c2 * c3 * r1 * r3 * r4;
+
+        // Bilinear transform, s = c (1 - z^-1) / (1 + z^-1)
+        let c = 2.0 * sr as f64;
+        let (c2s, c3s) = (c * c, c * c * c);
+        let bz = [
+            b1 * c + b2 * c2s + b3 * c3s,
+            b1 * c - b2 * c2s - 3.0 * b3 * c3s,
+            -b1 * c - b2 * c2s + 3.0 * b3 * c3s,
+            -b1 * c + b2 * c2s - b3 * c3s,
+        ];
+        let az = [
+            1.0 + a1 * c + a2 * c2s + a3 * c3s,
+            3.0 + a1 * c - a2 * c2s - 3.0 * a3 * c3s,
+            3.0 - a1 * c - a2 * c2s + 3.0 * a3 * c3s,
+            1.0 - a1 * c + a2 * c2s - a3 * c3s,
+        ];
+
+        let g = kind.makeup() / az[0];
+        self.b = bz.map(|v| v * g);
+        self.a = az.map(|v| v / az[0]);
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: S) -> S {
+        let (b, a) = (&self.b, &self.a);
+        x.map_lanes(&mut self.z, |z, x| {
+            let x = x as f64;
+            let y = b[0] * x + z[0];
+            z[0] = b[1] * x - a[1] * y + z[1];
+            z[1] = b[2] * x - a[2] * y + z[2];
+            z[2] = b[3] * x - a[3] * y;
+            flush_denormals(y as f32)
+        })
+    }
+}
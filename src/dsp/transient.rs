@@ -0,0 +1,93 @@
+use crate::dsp::simd::Sample;
+use crate::dsp::time_to_coeff;
+use crate::params::db_to_gain;
+use crate::values::ParamValues;
+
+/// Peak envelope follower with separate attack and release times.
+pub struct EnvFollower<S: Sample = f32> {
+    env: S,
+    attack_coeff: f32,
+    release_coeff: f32,
+}
+
+impl<S: Sample> EnvFollower<S> {
+    pub fn new() -> Self {
+        Self {
+            env: S::splat(0.0),
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+        }
+    }
+
+    pub fn set_times(&mut self, sr: f32, attack_s: f32, release_s: f32) {
+        self.attack_coeff = time_to_coeff(attack_s, sr);
+        self.release_coeff = time_to_coeff(release_s, sr);
+    }
+
+    pub fn reset(&mut self) {
+        self.env = S::splat(0.0);
+    }
+
+    #[inline]
+    pub fn process(&mut self, x: S) -> S {
+        let level = x.abs();
+        let coeff = S::select_gt(
+            level,
+            self.env,
+            S::splat(self.attack_coeff),
+            S::splat(self.release_coeff),
+        );
+        self.env = (level + (self.env - level) * coeff).flush_denormals();
+        self.env
+    }
+}
+
+/// Transient shaper: a fast and a slow envelope follower race each other.
+/// Where the fast one leads we're in a pick attack, where the slow one leads
+/// we're in the ringing sustain; each gets its own gain.
+pub struct TransientShaper<S: Sample = f32> {
+    sr: f32,
+    fast: EnvFollower<S>,
+    slow: EnvFollower<S>,
+}
+
+impl<S: Sample> TransientShaper<S> {
+    pub fn new(sr: f32) -> Self {
+        let mut t = Self {
+            sr,
+            fast: EnvFollower::new(),
+            slow: EnvFollower::new(),
+        };
+        t.reset(sr);
+        t
+    }
+
+    pub fn reset(&mut self, sr: f32) {
+        self.sr = sr;
+        self.fast.reset();
+        self.slow.reset();
+        self.fast.set_times(sr, 0.0005, 0.030);
+        self.slow.set_times(sr, 0.020, 0.250);
+    }
+
+    #[inline]
+    pub fn process_sample(&mut self, x: S, p: &ParamValues) -> S {
+        let attack_db = p.transient_attack;
+        let sustain_db = p.transient_sustain;
+
+        let fast = self.fast.process(x);
+        let slow = self.slow.process(x);
+
+        if attack_db == 0.0 && sustain_db == 0.0 {
+            return x;
+        }
+
+        let gain = fast.zip_map(slow, |fast, slow| {
+            // 0..1 measures of how "attacky" / "sustainy" the current moment is
+            let attack = ((fast - slow) / (fast + 1e-6)).clamp(0.0, 1.0);
+            let sustain = ((slow - fast) / (slow + 1e-6)).clamp(0.0, 1.0);
+            db_to_gain(attack_db * attack + sustain_db * sustain)
+        });
+        (x * gain).flush_denormals()
+    }
+}
@@ -1,20 +1,74 @@
 mod dsp;
 mod params;
+mod paths;
 mod presets;
+mod randomizer;
+mod scenes;
+mod state;
+mod user_presets;
+mod values;
 
 use nih_plug::prelude::*;
 use std::num::NonZeroU32;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
 
+use dsp::amp::{load_user_voicings, Voicing};
+use dsp::mix::Bypass;
+use dsp::nam::{list_models, NeuralSlot};
+use dsp::simd::F32x2;
 use dsp::{GuitarChain, StereoFx};
 use params::GtrParams;
+use scenes::Scenes;
+use user_presets::{PresetFile, PresetMeta};
+use values::ParamValues;
+
+/// Environment variable checked for a `.nam` file when the state has none.
+/// Only a fallback at runtime: it never ends up in the saved state.
+const NAM_MODEL_ENV: &str = "EDNITAR_NAM_MODEL";
+/// Samples between coefficient updates while a scene glides.
+const FADE_UPDATE_INTERVAL: usize = 32;
 
 pub struct Ednitar {
     params: Arc<GtrParams>,
     sample_rate: f32,
-    chain_l: GuitarChain,
-    chain_r: GuitarChain,
+    /// Left and right guitar chains, run side by side in SIMD lanes.
+    chain: GuitarChain<F32x2>,
     fx: StereoFx,
+    bypass: Bypass,
+    scenes: Scenes,
+    neural: Arc<NeuralSlot>,
+    /// Files `GtrParams::nam_model` picks from, scanned in `initialize`.
+    nam_models: Arc<RwLock<Vec<PathBuf>>>,
+    /// Last seen value of `GtrParams::nam_model`.
+    nam_model: i32,
+    /// Load the model from `nam_model_path` on the next block.
+    load_neural: bool,
+    /// Files `GtrParams::preset` picks from.
+    presets: Arc<RwLock<Vec<PathBuf>>>,
+    /// Preset loaded into a scene in the background, for the audio thread.
+    loaded_scene: Arc<Mutex<Option<(usize, ParamValues)>>>,
+    preset_save_held: bool,
+    preset_load_held: bool,
+}
+
+/// Work done off the audio thread.
+// Queued from the audio thread, so boxing the values would allocate there.
+#[allow(clippy::large_enum_variant)]
+pub enum Task {
+    /// Load the `.nam` model saved in the state, for this host sample rate.
+    LoadNeuralModel(f32),
+    /// Load this file from the models folder (by index) and save its path
+    /// in the state.
+    SelectNeuralModel(usize, f32),
+    /// Save a scene stored on the audio thread into the plugin state.
+    StoreScene(usize, ParamValues),
+    /// Save the seed of a new random variation into the plugin state.
+    StoreRandomSeed(u32),
+    /// Save the live params as a new user preset.
+    SavePreset,
+    /// Load a user preset (by index) into a scene (0-based).
+    LoadPreset(usize, usize),
 }
 
 impl Default for Ednitar {
@@ -23,13 +77,97 @@ impl Default for Ednitar {
         Self {
             params: Arc::new(GtrParams::default()),
             sample_rate: sr,
-            chain_l: GuitarChain::new(sr),
-            chain_r: GuitarChain::new(sr),
+            chain: GuitarChain::new(sr),
             fx: StereoFx::new(sr),
+            bypass: Bypass::new(sr),
+            scenes: Scenes::new(),
+            neural: Arc::new(NeuralSlot::default()),
+            nam_models: Arc::new(RwLock::new(Vec::new())),
+            nam_model: 0,
+            load_neural: false,
+            presets: Arc::new(RwLock::new(Vec::new())),
+            loaded_scene: Arc::new(Mutex::new(None)),
+            preset_save_held: false,
+            preset_load_held: false,
         }
     }
 }
 
+impl Ednitar {
+    /// Sizes every buffer for `sr` and clears the DSP state. Allocates.
+    fn initialize_dsp(&mut self, sr: f32) {
+        self.sample_rate = sr;
+        self.chain.reset(sr);
+        self.fx.initialize(sr);
+        self.bypass.reset(sr);
+    }
+
+    /// Per-block coefficient updates.
+    fn update_params(&mut self, v: &ParamValues) {
+        self.chain.update_params(v);
+        self.fx.update_params(v);
+        let chain_latency = self.chain.latency();
+        self.fx.set_dry_latency(chain_latency);
+        self.bypass.set_latency(self.fx.latency(chain_latency));
+    }
+
+    /// One stereo frame through bypass, chain and effects.
+    #[inline]
+    fn process_frame(
+        &mut self,
+        in_l: f32,
+        in_r: f32,
+        v: &ParamValues,
+        bypassed: bool,
+    ) -> (f32, f32) {
+        // While bypassed the processing only gets silence, so its tails ring
+        // out on top of the dry signal until the tail time is up
+        let (proc_gain, wet_gain, (byp_l, byp_r)) = self.bypass.process(in_l, in_r, bypassed);
+        let in_l = in_l * proc_gain;
+        let in_r = in_r * proc_gain;
+
+        let F32x2([amp_l, amp_r]) = self.chain.process_sample(F32x2::new(in_l, in_r), v);
+
+        let (out_l, out_r) = self.fx.process_frame(in_l, in_r, amp_l, amp_r, v);
+        let out_gain = wet_gain * self.scenes.gain();
+        (out_l * out_gain + byp_l, out_r * out_gain + byp_r)
+    }
+}
+
+/// Runs `f` with allocations caught under the `assert_process_allocs`
+/// feature, the way the wrapper already runs `process`.
+#[inline]
+fn no_alloc<T>(f: impl FnOnce() -> T) -> T {
+    #[cfg(feature = "assert_process_allocs")]
+    return assert_no_alloc::assert_no_alloc(f);
+    #[cfg(not(feature = "assert_process_allocs"))]
+    f()
+}
+
+/// The `.nam` file saved in the state, else the one `NAM_MODEL_ENV` names.
+fn nam_model_path(params: &GtrParams) -> Option<String> {
+    params
+        .nam_path
+        .read()
+        .ok()
+        .and_then(|p| p.clone())
+        .or_else(|| std::env::var(NAM_MODEL_ENV).ok())
+}
+
+/// Lists the presets folder into `presets` and the Preset param's names.
+/// Blocking; keep it off the audio thread.
+fn scan_presets(params: &GtrParams, presets: &RwLock<Vec<PathBuf>>) {
+    let found = paths::presets_dir()
+        .map(|dir| user_presets::list(&dir))
+        .unwrap_or_default();
+    if let Ok(mut names) = params.preset_names.write() {
+        *names = found.iter().map(|(_, meta)| meta.name.clone()).collect();
+    }
+    if let Ok(mut presets) = presets.write() {
+        *presets = found.into_iter().map(|(path, _)| path).collect();
+    }
+}
+
 impl Plugin for Ednitar {
     const NAME: &'static str = "Ednitar";
     const VENDOR: &'static str = "me";
@@ -51,12 +189,107 @@ impl Plugin for Ednitar {
     const SAMPLE_ACCURATE_AUTOMATION: bool = true;
 
     type SysExMessage = ();
-    type BackgroundTask = ();
+    type BackgroundTask = Task;
 
     fn params(&self) -> Arc<dyn Params> {
         self.params.clone()
     }
 
+    fn filter_state(state: &mut PluginState) {
+        state::migrate(state);
+    }
+
+    fn task_executor(&mut self) -> TaskExecutor<Self> {
+        let neural = self.neural.clone();
+        let nam_models = self.nam_models.clone();
+        let presets = self.presets.clone();
+        let loaded_scene = self.loaded_scene.clone();
+        let params = self.params.clone();
+        let load_neural = move |path: &str, sample_rate: f32| match neural.load(path, sample_rate) {
+            Ok(()) => {
+                nih_log!("Loaded neural amp model {path}");
+                true
+            }
+            Err(e) => {
+                nih_error!("Could not load neural amp model: {e}");
+                false
+            }
+        };
+        Box::new(move |task| match task {
+            Task::LoadNeuralModel(sample_rate) => {
+                if let Some(path) = nam_model_path(&params) {
+                    load_neural(&path, sample_rate);
+                }
+            }
+            Task::SelectNeuralModel(index, sample_rate) => {
+                let path = nam_models.read().ok().and_then(|m| m.get(index).cloned());
+                if let Some(path) = path {
+                    let path = path.to_string_lossy().into_owned();
+                    if load_neural(&path, sample_rate) {
+                        if let Ok(mut saved) = params.nam_path.write() {
+                            *saved = Some(path);
+                        }
+                    }
+                }
+            }
+            Task::StoreScene(index, values) => {
+                let snapshot = values.to_snapshot(&params);
+                if let Ok(mut scenes) = params.scenes.write() {
+                    scenes.resize(scenes::MAX_SCENES, None);
+                    scenes[index] = Some(snapshot);
+                }
+            }
+            Task::StoreRandomSeed(seed) => {
+                if let Ok(mut saved) = params.random_seed.write() {
+                    *saved = seed;
+                }
+            }
+            Task::SavePreset => {
+                let Some(dir) = paths::presets_dir() else {
+                    return;
+                };
+                let meta = PresetMeta {
+                    name: user_presets::unused_name(&dir, &user_presets::list(&dir)),
+                    ..PresetMeta::default()
+                };
+                match PresetFile::capture(meta, &*params).save(&dir) {
+                    Ok(path) => nih_log!("Saved preset {}", path.display()),
+                    Err(e) => nih_error!("Could not save preset: {e}"),
+                }
+                scan_presets(&params, &presets);
+            }
+            Task::LoadPreset(preset, scene) => {
+                let path = presets.read().ok().and_then(|p| p.get(preset).cloned());
+                let Some(path) = path else {
+                    return;
+                };
+                let file = match PresetFile::load(&path) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        nih_error!("Could not load preset {e}");
+                        return;
+                    }
+                };
+                let (snapshot, skipped) = file.to_snapshot(&*params);
+                if !skipped.is_empty() {
+                    nih_log!(
+                        "Preset {} has values this build can't use: {}",
+                        path.display(),
+                        skipped.join(", ")
+                    );
+                }
+                let values = ParamValues::from_snapshot(&params, &snapshot);
+                if let Ok(mut scenes) = params.scenes.write() {
+                    scenes.resize(scenes::MAX_SCENES, None);
+                    scenes[scene] = Some(values.to_snapshot(&params));
+                }
+                if let Ok(mut loaded) = loaded_scene.lock() {
+                    *loaded = Some((scene, values));
+                }
+            }
+        })
+    }
+
     fn initialize(
         &mut self,
         _io: &AudioIOLayout,
@@ -65,32 +298,141 @@ impl Plugin for Ednitar {
     ) -> bool {
         self.sample_rate = buffer_config.sample_rate;
 
-        self.chain_l.reset(self.sample_rate);
-        self.chain_r.reset(self.sample_rate);
-        self.fx.reset(self.sample_rate);
+        // Models trained at another rate can't stay after a rate change
+        for model in self.chain.neural_models_mut().iter_mut() {
+            if model.as_ref().is_some_and(|m| !m.runs_at(self.sample_rate)) {
+                *model = None;
+            }
+        }
+        self.neural.clear_pending();
+
+        let models = paths::nam_models_dir()
+            .map(|dir| list_models(&dir))
+            .unwrap_or_default();
+        if let Ok(mut names) = self.params.nam_model_names.write() {
+            *names = models
+                .iter()
+                .map(|p| {
+                    p.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+        }
+        if let Ok(mut nam_models) = self.nam_models.write() {
+            *nam_models = models;
+        }
+        self.nam_model = self.params.nam_model.value();
+
+        // (Re)load the neural amp model named in the state. The first block
+        // queues it, since loading takes too long to block activation.
+        self.load_neural = nam_model_path(&self.params).is_some();
+
+        // Scenes come from the (possibly just loaded) state
+        self.scenes.load(&self.params);
+        scan_presets(&self.params, &self.presets);
+        self.preset_save_held = self.params.preset_save.value();
+        self.preset_load_held = self.params.preset_load.value();
+
+        // Pick up voicing files added since the last activation
+        let voicings: Arc<[Voicing]> = paths::voicings_dir()
+            .map(|dir| load_user_voicings(&dir))
+            .unwrap_or_default()
+            .into();
+        if let Ok(mut names) = self.params.user_voicing_names.write() {
+            *names = voicings.iter().map(|v| v.name.to_string()).collect();
+        }
+        self.chain.set_user_voicings(voicings);
+
+        self.initialize_dsp(self.sample_rate);
 
         true
     }
 
+    /// Buffers are sized for the rate given to `initialize`. Hosts only
+    /// change the rate by initializing again, so they never need to grow
+    /// here.
     fn reset(&mut self) {
-        self.chain_l.reset(self.sample_rate);
-        self.chain_r.reset(self.sample_rate);
-        self.fx.reset(self.sample_rate);
+        no_alloc(|| {
+            self.chain.reset(self.sample_rate);
+            self.fx.reset(self.sample_rate);
+            self.bypass.reset(self.sample_rate);
+        })
     }
 
     fn process(
         &mut self,
         buffer: &mut Buffer<'_>,
         _aux: &mut AuxiliaryBuffers<'_>,
-        _ctx: &mut impl ProcessContext<Self>,
+        ctx: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
         let params = self.params.clone();
 
+        let nam_model = params.nam_model.value();
+        if nam_model != self.nam_model {
+            self.nam_model = nam_model;
+            let index = (nam_model - 1).max(0) as usize;
+            ctx.execute_background(Task::SelectNeuralModel(index, self.sample_rate));
+        } else if self.load_neural {
+            ctx.execute_background(Task::LoadNeuralModel(self.sample_rate));
+        }
+        self.load_neural = false;
+
+        let [neural_l, neural_r] = self.chain.neural_models_mut();
+        self.neural.try_swap(neural_l, neural_r);
+
+        let transport = ctx.transport();
+        self.fx
+            .set_transport(transport.tempo, transport.pos_beats(), transport.playing);
+
+        if let Ok(mut loaded) = self.loaded_scene.try_lock() {
+            if let Some((index, values)) = loaded.take() {
+                self.scenes
+                    .load_slot(self.sample_rate, &params, index, values);
+            }
+        }
+        let save = params.preset_save.value();
+        if save && !self.preset_save_held {
+            ctx.execute_background(Task::SavePreset);
+        }
+        self.preset_save_held = save;
+        let load = params.preset_load.value();
+        let scene = params.scene.value();
+        if load && !self.preset_load_held && scene > 0 {
+            let preset = (params.preset.value() - 1).max(0) as usize;
+            ctx.execute_background(Task::LoadPreset(preset, scene as usize - 1));
+        }
+        self.preset_load_held = load;
+
+        if let Some((index, values)) = self.scenes.update(self.sample_rate, &params) {
+            ctx.execute_background(Task::StoreScene(index, values));
+        }
+        if let Some(seed) = self.scenes.take_rolled_seed() {
+            ctx.execute_background(Task::StoreRandomSeed(seed));
+        }
+
+        // CLAP param modulation goes on top of whatever the scenes picked.
+        // With sample-accurate automation the block is split at every
+        // modulation event, so this is exact to the sample.
+        let mods = ParamValues::modulation_offsets(&params);
+        let mut values = self.scenes.current().with_modulation(&params, &mods);
+
         // Update per-block coefficients
-        self.chain_l.update_params(params.as_ref());
-        self.chain_r.update_params(params.as_ref());
+        self.update_params(&values);
+        let bypassed = params.bypass.value();
+
+        for (i, mut frame) in buffer.iter_samples().enumerate() {
+            if self.scenes.is_fading() {
+                values = self.scenes.next().with_modulation(&params, &mods);
+                // Scene glides also move filter coefficients, and stepped
+                // values switch while the output is silent
+                if i % FADE_UPDATE_INTERVAL == 0 || self.scenes.gain() == 0.0 {
+                    self.update_params(&values);
+                }
+            }
+            let v = &values;
 
-        for mut frame in buffer.iter_samples() {
             let mut it = frame.iter_mut();
             if let Some(l_s) = it.next() {
                 let r_s_opt = it.next();
@@ -98,11 +440,7 @@ impl Plugin for Ednitar {
                 let in_l = *l_s;
                 let in_r = r_s_opt.as_deref().copied().unwrap_or(in_l);
 
-                let dry_l = self.chain_l.process_sample(in_l, params.as_ref());
-                let dry_r = self.chain_r.process_sample(in_r, params.as_ref());
-
-                let (out_l, out_r) = self.fx.process_frame(dry_l, dry_r, params.as_ref());
-
+                let (out_l, out_r) = self.process_frame(in_l, in_r, v, bypassed);
                 *l_s = out_l;
                 if let Some(r_s) = r_s_opt {
                     *r_s = out_r;
@@ -125,12 +463,161 @@ impl ClapPlugin for Ednitar {
         ClapFeature::Distortion,
     ];
 
+    // There are no voices to modulate, so only mono modulation applies
     const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = None;
 
-    fn remote_controls(&self, _context: &mut impl RemoteControlsContext) {}
+    /// Eight controls per page, most used first, so controllers and headless
+    /// hosts can map knobs without setup.
+    fn remote_controls(&self, context: &mut impl RemoteControlsContext) {
+        let p = &self.params;
+
+        context.add_section("Main", |section| {
+            section.add_page("Macros", |page| {
+                page.add_param(&p.drive);
+                page.add_param(&p.tone);
+                page.add_param(&p.space);
+                page.add_param(&p.width);
+                page.add_param(&p.input_gain);
+                page.add_param(&p.output_gain);
+                page.add_param(&p.limiter_on);
+                page.add_param(&p.mix);
+            });
+            section.add_page("Amp", |page| {
+                page.add_param(&p.amp_model);
+                page.add_param(&p.user_voicing);
+                page.add_param(&p.nam_model);
+                page.add_spacer();
+                page.add_param(&p.bass);
+                page.add_param(&p.mid);
+                page.add_param(&p.treble);
+                page.add_param(&p.sag);
+            });
+            section.add_page("Cab & Neural Trim", |page| {
+                page.add_param(&p.cab_model);
+                page.add_param(&p.presence);
+                page.add_param(&p.air);
+                page.add_param(&p.low_cut_hz);
+                page.add_param(&p.nam_input_db);
+                page.add_param(&p.nam_output_db);
+            });
+            section.add_page("Dynamics", |page| {
+                page.add_param(&p.gate_threshold);
+                page.add_param(&p.gate_release_ms);
+                page.add_spacer();
+                page.add_spacer();
+                page.add_param(&p.transient_attack);
+                page.add_param(&p.transient_sustain);
+                page.add_param(&p.transient_placement);
+            });
+        });
+
+        context.add_section("FX", |section| {
+            section.add_page("Stutter & Freeze", |page| {
+                page.add_param(&p.stutter_on);
+                page.add_param(&p.stutter_div);
+                page.add_param(&p.stutter_repeats);
+                page.add_param(&p.stutter_pitch);
+                page.add_param(&p.stutter_decay);
+                page.add_param(&p.freeze_on);
+                page.add_param(&p.freeze_level);
+                page.add_param(&p.freeze_fade_ms);
+            });
+            section.add_page("Tape Stop", |page| {
+                page.add_param(&p.tapestop_on);
+                page.add_param(&p.tapestop_mode);
+                page.add_param(&p.tapestop_time_ms);
+                page.add_param(&p.tapestop_curve);
+                page.add_param(&p.tapestop_spinup_ms);
+            });
+            section.add_page("Crusher", |page| {
+                page.add_param(&p.crush_mix);
+                page.add_param(&p.crush_bits);
+                page.add_param(&p.crush_rate_hz);
+                page.add_param(&p.crush_jitter);
+                page.add_param(&p.crush_filter);
+                page.add_param(&p.crush_placement);
+            });
+            section.add_page("Space", |page| {
+                page.add_param(&p.space_mode);
+                page.add_param(&p.shimmer_amount);
+                page.add_param(&p.shimmer_decay);
+                page.add_param(&p.shimmer_interval);
+                page.add_param(&p.shimmer_highcut_hz);
+                page.add_param(&p.double_amount);
+                page.add_param(&p.double_time_ms);
+                page.add_param(&p.double_drift);
+            });
+            section.add_page("Routing", |page| {
+                page.add_param(&p.chain_slot_1);
+                page.add_param(&p.chain_slot_2);
+                page.add_param(&p.chain_slot_3);
+                page.add_param(&p.chain_slot_4);
+                page.add_param(&p.mix_law);
+                page.add_param(&p.kill_dry);
+            });
+            section.add_page("FX Order", |page| {
+                page.add_param(&p.fx_slot_1);
+                page.add_param(&p.fx_slot_2);
+                page.add_param(&p.fx_slot_3);
+                page.add_param(&p.fx_slot_4);
+                page.add_param(&p.fx_slot_5);
+                page.add_param(&p.fx_slot_6);
+            });
+        });
+
+        context.add_section("Performance", |section| {
+            section.add_page("Scenes & Morph", |page| {
+                page.add_param(&p.scene);
+                page.add_param(&p.scene_store);
+                page.add_param(&p.scene_fade_ms);
+                page.add_spacer();
+                page.add_param(&p.morph_mode);
+                page.add_param(&p.morph_x);
+                page.add_param(&p.morph_y);
+            });
+            section.add_page("Random & Presets", |page| {
+                page.add_param(&p.random_on);
+                page.add_param(&p.random_roll);
+                page.add_param(&p.preset);
+                page.add_param(&p.preset_load);
+                page.add_param(&p.random_lock_amp);
+                page.add_param(&p.random_lock_cab);
+                page.add_param(&p.random_lock_space);
+                page.add_param(&p.preset_save);
+            });
+        });
+    }
 
     const CLAP_MANUAL_URL: Option<&'static str> = Some("Not yet");
     const CLAP_SUPPORT_URL: Option<&'static str> = Some("Not yet");
 }
 
 nih_export_clap!(Ednitar);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails on any allocation when run with `--features assert_process_allocs`.
+    #[test]
+    fn reset_and_process_do_not_allocate() {
+        let mut plugin = Ednitar::default();
+        let params = plugin.params.clone();
+        for sr in [44100.0, 96000.0, 48000.0] {
+            plugin.initialize_dsp(sr);
+            for cycle in 0..4 {
+                no_alloc(|| {
+                    plugin.reset();
+                    plugin.scenes.update(sr, &params);
+                    let v = *plugin.scenes.current();
+                    plugin.update_params(&v);
+                    for i in 0..512 {
+                        let x = 0.5 * (i as f32 * 0.05).sin();
+                        let (l, r) = plugin.process_frame(x, x, &v, cycle % 2 == 1);
+                        assert!(l.is_finite() && r.is_finite());
+                    }
+                })
+            }
+        }
+    }
+}
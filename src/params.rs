@@ -1,7 +1,87 @@
 use nih_plug::prelude::*;
+use std::sync::{Arc, RwLock};
+
+use crate::dsp::amp::MAX_USER_VOICINGS;
+use crate::dsp::nam::MAX_NAM_MODELS;
+use crate::scenes::MAX_SCENES;
+use crate::state::STATE_VERSION;
+use crate::user_presets::MAX_PRESETS;
+use crate::values::Snapshot;
 
 #[derive(Params)]
 pub struct GtrParams {
+    /// Host bypass. Crossfades to the dry signal and lets FX tails ring out.
+    #[id = "edm_bypass"]
+    pub bypass: BoolParam,
+
+    /// Active scene. 0 = live params, 1..8 = stored scenes (an empty scene
+    /// also plays the live params).
+    #[id = "edm_scene"]
+    pub scene: IntParam,
+
+    /// Rising edge stores the live params into the selected scene.
+    #[id = "edm_scene_store"]
+    pub scene_store: BoolParam,
+
+    /// Glide time between scenes, in ms.
+    #[id = "edm_scene_fade"]
+    pub scene_fade_ms: FloatParam,
+
+    /// Morph across the stored scenes instead of picking one: A/B blends
+    /// scenes 1 and 2 with Morph X, XY spreads scenes 1-4 over the X/Y square
+    /// (1 at X=0/Y=0, 2 at X=1/Y=0, 3 at X=0/Y=1, 4 at X=1/Y=1).
+    #[id = "edm_morph_mode"]
+    pub morph_mode: EnumParam<MorphMode>,
+
+    #[id = "edm_morph_x"]
+    pub morph_x: FloatParam,
+
+    #[id = "edm_morph_y"]
+    pub morph_y: FloatParam,
+
+    /// Play a seeded random variation of the current sound, see `randomizer`.
+    #[id = "edm_random_on"]
+    pub random_on: BoolParam,
+
+    /// Rising edge rolls a new random variation.
+    #[id = "edm_random_roll"]
+    pub random_roll: BoolParam,
+
+    /// Keep amp model, drive, amp EQ, sag and low cut as they are.
+    #[id = "edm_random_lock_amp"]
+    pub random_lock_amp: BoolParam,
+
+    /// Keep cab model, tone, presence and air as they are.
+    #[id = "edm_random_lock_cab"]
+    pub random_lock_cab: BoolParam,
+
+    /// Keep space, width, doubler and shimmer settings as they are.
+    #[id = "edm_random_lock_space"]
+    pub random_lock_space: BoolParam,
+
+    /// Seed of the current random variation, so it comes back with the project.
+    #[persist = "random_seed"]
+    pub random_seed: Arc<RwLock<u32>>,
+
+    /// Stored scenes, see `scenes::Scenes`.
+    #[persist = "scenes"]
+    pub scenes: Arc<RwLock<Vec<Option<Snapshot>>>>,
+
+    /// Which user preset (in name order) Load Preset loads.
+    #[id = "edm_preset"]
+    pub preset: IntParam,
+
+    /// Names shown by `preset`, filled in when the presets folder is scanned.
+    pub preset_names: FileNames,
+
+    /// Rising edge saves the live params as a new user preset.
+    #[id = "edm_preset_save"]
+    pub preset_save: BoolParam,
+
+    /// Rising edge loads the selected preset into the selected scene.
+    #[id = "edm_preset_load"]
+    pub preset_load: BoolParam,
+
     /// Input trim before everything else (useful for hot DI vs quiet loops)
     #[id = "edm_in_gain"]
     pub input_gain: FloatParam,
@@ -39,10 +119,59 @@ pub struct GtrParams {
     #[id = "edm_gate_release"]
     pub gate_release_ms: FloatParam,
 
-    /// Amp "type" (all share one engine but different internal voicings).
+    /// Amp "type": each model is its own cascade of gain stages and waveshapers.
     #[id = "edm_amp_model"]
     pub amp_model: EnumParam<AmpModel>,
 
+    /// Which user voicing file (in name order) the User amp model plays.
+    #[id = "edm_user_voicing"]
+    pub user_voicing: IntParam,
+
+    /// Names shown by `user_voicing`, filled in when the voicings are loaded.
+    pub user_voicing_names: FileNames,
+
+    /// Amp tone stack bass. The stack circuit follows the amp model.
+    #[id = "edm_bass"]
+    pub bass: FloatParam,
+
+    /// Amp tone stack mids.
+    #[id = "edm_mid"]
+    pub mid: FloatParam,
+
+    /// Amp tone stack treble.
+    #[id = "edm_treble"]
+    pub treble: FloatParam,
+
+    /// Level into the neural amp model, in dB. Match it to how hot the DI was
+    /// when the model was captured.
+    #[id = "edm_nam_in"]
+    pub nam_input_db: FloatParam,
+
+    /// Level out of the neural amp model, in dB, on top of its own loudness
+    /// normalization.
+    #[id = "edm_nam_out"]
+    pub nam_output_db: FloatParam,
+
+    /// Layout version of the saved state, see `state::migrate`.
+    #[persist = "state_version"]
+    pub state_version: Arc<RwLock<u32>>,
+
+    /// Path of the `.nam` file used by the Neural amp model.
+    #[persist = "nam_path"]
+    pub nam_path: Arc<RwLock<Option<String>>>,
+
+    /// Which `.nam` file (in name order) from the models folder to load.
+    /// Changing it loads that file and saves its path in `nam_path`.
+    #[id = "edm_nam_model"]
+    pub nam_model: IntParam,
+
+    /// Names shown by `nam_model`, filled in when the models folder is scanned.
+    pub nam_model_names: FileNames,
+
+    /// Power supply sag. 0 = stiff and tight, 1 = spongy, compresses when you dig in.
+    #[id = "edm_sag"]
+    pub sag: FloatParam,
+
     /// Pre-amp low-cut (IIR HPF) frequency, in Hz.
     #[id = "edm_low_cut_hz"]
     pub low_cut_hz: FloatParam,
@@ -59,9 +188,182 @@ pub struct GtrParams {
     #[id = "edm_cab_model"]
     pub cab_model: EnumParam<CabModel>,
 
+    /// Global dry/wet. 0 = clean input only, 1 = fully processed.
+    #[id = "edm_mix"]
+    pub mix: FloatParam,
+
+    /// How Mix crossfades: linear (sums flat for correlated signals) or
+    /// equal power (constant loudness for the uncorrelated amp and DI).
+    #[id = "edm_mix_law"]
+    pub mix_law: EnumParam<MixLaw>,
+
+    /// Drop the clean input from the output, e.g. on an FX send. Mix still
+    /// sets the processed level.
+    #[id = "edm_kill_dry"]
+    pub kill_dry: BoolParam,
+
     /// Safety limiter toggle. On by default for beginners.
     #[id = "edm_limiter_on"]
     pub limiter_on: BoolParam,
+
+    /// Pick attack boost/cut in dB. Positive = snappier, negative = softer.
+    #[id = "edm_trans_attack"]
+    pub transient_attack: FloatParam,
+
+    /// Sustain boost/cut in dB. Positive = more ring, negative = tighter.
+    #[id = "edm_trans_sustain"]
+    pub transient_sustain: FloatParam,
+
+    /// Transient shaper position: before the amp (shapes what hits the
+    /// distortion) or after the cab (mix punch).
+    #[id = "edm_trans_place"]
+    pub transient_placement: EnumParam<TransientPlacement>,
+
+    // ==========
+    // FX
+    // ==========
+    /// Beat repeat trigger. While held, a slice is grabbed at the next grid line
+    /// and looped.
+    #[id = "edm_stutter_on"]
+    pub stutter_on: BoolParam,
+
+    /// Beat repeat slice length as a note division.
+    #[id = "edm_stutter_div"]
+    pub stutter_div: EnumParam<StutterDivision>,
+
+    /// How many times a slice repeats before a new one is grabbed.
+    #[id = "edm_stutter_repeats"]
+    pub stutter_repeats: IntParam,
+
+    /// Pitch step per repeat, in semitones. Negative values fall, positive rise.
+    #[id = "edm_stutter_pitch"]
+    pub stutter_pitch: FloatParam,
+
+    /// Level drop per repeat. 0 = every repeat equally loud.
+    #[id = "edm_stutter_decay"]
+    pub stutter_decay: FloatParam,
+
+    /// Tape stop trigger. Slows the tape down while held, spins back up on release.
+    #[id = "edm_tapestop_on"]
+    pub tapestop_on: BoolParam,
+
+    /// Full stop ("powerdown") or settle at half speed.
+    #[id = "edm_tapestop_mode"]
+    pub tapestop_mode: EnumParam<TapeStopMode>,
+
+    /// Time to reach the target speed, in ms.
+    #[id = "edm_tapestop_time"]
+    pub tapestop_time_ms: FloatParam,
+
+    /// Slowdown curve. 0 = linear, 1 = fast drop with a long tail.
+    #[id = "edm_tapestop_curve"]
+    pub tapestop_curve: FloatParam,
+
+    /// Time to get back up to speed after release, in ms.
+    #[id = "edm_tapestop_spinup"]
+    pub tapestop_spinup_ms: FloatParam,
+
+    /// Bitcrusher amount. 0 = off, 1 = fully crushed.
+    #[id = "edm_crush_mix"]
+    pub crush_mix: FloatParam,
+
+    /// Crusher bit depth. Fractional values give in-between step sizes.
+    #[id = "edm_crush_bits"]
+    pub crush_bits: FloatParam,
+
+    /// Crusher target sample rate, in Hz.
+    #[id = "edm_crush_rate"]
+    pub crush_rate_hz: FloatParam,
+
+    /// Random wobble of the crusher sample clock. 0 = steady.
+    #[id = "edm_crush_jitter"]
+    pub crush_jitter: FloatParam,
+
+    /// Low-pass filtering around the crusher to tame harshness.
+    #[id = "edm_crush_filter"]
+    pub crush_filter: EnumParam<CrushFilter>,
+
+    /// Where the crusher sits in the signal path.
+    #[id = "edm_crush_place"]
+    pub crush_placement: EnumParam<CrushPlacement>,
+
+    /// Doubler amount. 0 = off, 1 = right side is fully the second take.
+    #[id = "edm_double_amount"]
+    pub double_amount: FloatParam,
+
+    /// How far the second take trails the first, in ms.
+    #[id = "edm_double_time"]
+    pub double_time_ms: FloatParam,
+
+    /// Random timing/pitch wander of the second take.
+    #[id = "edm_double_drift"]
+    pub double_drift: FloatParam,
+
+    /// Character of the "Space" section.
+    #[id = "edm_space_mode"]
+    pub space_mode: EnumParam<SpaceMode>,
+
+    /// Shimmer: how much of the reverb feedback goes through the pitch shifter.
+    #[id = "edm_shimmer_amount"]
+    pub shimmer_amount: FloatParam,
+
+    /// Shimmer tail length. 0 = short, 1 = very long (but always decaying).
+    #[id = "edm_shimmer_decay"]
+    pub shimmer_decay: FloatParam,
+
+    /// Shimmer pitch interval per trip round the feedback loop.
+    #[id = "edm_shimmer_interval"]
+    pub shimmer_interval: EnumParam<ShimmerInterval>,
+
+    /// Shimmer feedback high-cut, in Hz. Lower = darker, less fizzy tails.
+    #[id = "edm_shimmer_highcut"]
+    pub shimmer_highcut_hz: FloatParam,
+
+    /// Spectral freeze trigger. Each press grabs the current sound as a new pad.
+    #[id = "edm_freeze_on"]
+    pub freeze_on: BoolParam,
+
+    /// Level of the frozen pad on top of the live signal. 0 = freeze off.
+    #[id = "edm_freeze_level"]
+    pub freeze_level: FloatParam,
+
+    /// Pad swell/release and freeze-to-freeze crossfade time, in ms.
+    #[id = "edm_freeze_fade"]
+    pub freeze_fade_ms: FloatParam,
+
+    /// Guitar chain order, slot by slot. A module picked twice only runs in
+    /// its first slot; modules nobody picked run after the slots.
+    #[id = "edm_chain_slot1"]
+    pub chain_slot_1: EnumParam<ChainModule>,
+
+    #[id = "edm_chain_slot2"]
+    pub chain_slot_2: EnumParam<ChainModule>,
+
+    #[id = "edm_chain_slot3"]
+    pub chain_slot_3: EnumParam<ChainModule>,
+
+    #[id = "edm_chain_slot4"]
+    pub chain_slot_4: EnumParam<ChainModule>,
+
+    /// Stereo FX order, same rules as the chain slots. Output gain, limiter
+    /// and the safety clipper always come last.
+    #[id = "edm_fx_slot1"]
+    pub fx_slot_1: EnumParam<FxModule>,
+
+    #[id = "edm_fx_slot2"]
+    pub fx_slot_2: EnumParam<FxModule>,
+
+    #[id = "edm_fx_slot3"]
+    pub fx_slot_3: EnumParam<FxModule>,
+
+    #[id = "edm_fx_slot4"]
+    pub fx_slot_4: EnumParam<FxModule>,
+
+    #[id = "edm_fx_slot5"]
+    pub fx_slot_5: EnumParam<FxModule>,
+
+    #[id = "edm_fx_slot6"]
+    pub fx_slot_6: EnumParam<FxModule>,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Enum)]
@@ -72,6 +374,11 @@ pub enum AmpModel {
     CrunchTight,
     /// Focused high-gain for leads and big EDM hooks.
     LeadEdm,
+    /// A Neural Amp Modeler capture loaded from a `.nam` file.
+    Neural,
+    /// A voicing loaded from the user's voicings folder.
+    #[name = "User Voicing"]
+    User,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Enum)]
@@ -86,9 +393,235 @@ pub enum CabModel {
     DiBypass,
 }
 
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum MorphMode {
+    Off,
+    #[name = "A/B"]
+    AB,
+    #[name = "XY"]
+    XY,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum MixLaw {
+    Linear,
+    #[name = "Equal Power"]
+    EqualPower,
+}
+
+pub const CHAIN_SLOTS: usize = 4;
+pub const DEFAULT_CHAIN_ORDER: [ChainModule; CHAIN_SLOTS] = [
+    ChainModule::Gate,
+    ChainModule::LowCut,
+    ChainModule::Amp,
+    ChainModule::Cab,
+];
+
+/// Modules of the per-channel guitar chain. Pre-amp/post-cab placements of
+/// the transient shaper and crusher move with the amp and cab.
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum ChainModule {
+    Gate,
+    #[name = "Low Cut"]
+    LowCut,
+    Amp,
+    Cab,
+}
+
+pub const FX_SLOTS: usize = 6;
+pub const DEFAULT_FX_ORDER: [FxModule; FX_SLOTS] = [
+    FxModule::Stutter,
+    FxModule::TapeStop,
+    FxModule::Doubler,
+    FxModule::Freeze,
+    FxModule::Space,
+    FxModule::Width,
+];
+
+/// Modules of the stereo FX section.
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum FxModule {
+    Stutter,
+    #[name = "Tape Stop"]
+    TapeStop,
+    Doubler,
+    Freeze,
+    Space,
+    Width,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum TransientPlacement {
+    #[name = "Pre Amp"]
+    PreAmp,
+    #[name = "Post Cab"]
+    PostCab,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum StutterDivision {
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/8T"]
+    EighthTriplet,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/16T"]
+    SixteenthTriplet,
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl StutterDivision {
+    /// Length of one division in quarter-note beats.
+    pub fn beats(self) -> f64 {
+        match self {
+            StutterDivision::Quarter => 1.0,
+            StutterDivision::Eighth => 0.5,
+            StutterDivision::EighthTriplet => 1.0 / 3.0,
+            StutterDivision::Sixteenth => 0.25,
+            StutterDivision::SixteenthTriplet => 1.0 / 6.0,
+            StutterDivision::ThirtySecond => 0.125,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum TapeStopMode {
+    /// Slow down to a complete halt.
+    #[name = "Tape Stop"]
+    Stop,
+    /// Slow down to half speed (an octave down) and stay there.
+    #[name = "Half Time"]
+    HalfTime,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum CrushFilter {
+    /// Raw steps and aliasing.
+    Off,
+    /// Anti-alias low-pass before the sample & hold.
+    Pre,
+    /// Smoothing low-pass after the sample & hold.
+    Post,
+    /// Both filters.
+    Both,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum CrushPlacement {
+    /// Crush the DI before it hits the amp.
+    #[name = "Pre Amp"]
+    PreAmp,
+    /// Crush the amp + cab sound.
+    #[name = "Post Cab"]
+    PostCab,
+    /// Crush everything including space, before output gain and limiter.
+    #[name = "Post FX"]
+    PostFx,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum SpaceMode {
+    /// Short stereo feedback delays, the classic Ednitar room.
+    Echo,
+    /// Reverb with pitch-shifted feedback for octave-up tails.
+    Shimmer,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Enum)]
+pub enum ShimmerInterval {
+    #[name = "+12 st"]
+    Octave,
+    #[name = "+7 st"]
+    Fifth,
+    #[name = "+19 st"]
+    OctaveFifth,
+}
+
+impl ShimmerInterval {
+    pub fn semitones(self) -> f32 {
+        match self {
+            ShimmerInterval::Octave => 12.0,
+            ShimmerInterval::Fifth => 7.0,
+            ShimmerInterval::OctaveFifth => 19.0,
+        }
+    }
+}
+
 impl Default for GtrParams {
     fn default() -> Self {
+        let preset_names = FileNames::default();
+        let user_voicing_names = FileNames::default();
+        let nam_model_names = FileNames::default();
+
         Self {
+            bypass: BoolParam::new("Bypass", false).make_bypass(),
+
+            scene: IntParam::new(
+                "Scene",
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_SCENES as i32,
+                },
+            )
+            .with_value_to_string(Arc::new(|v| match v {
+                0 => "Live".to_string(),
+                n => n.to_string(),
+            }))
+            .with_string_to_value(Arc::new(|s| {
+                let s = s.trim();
+                if s.eq_ignore_ascii_case("live") {
+                    Some(0)
+                } else {
+                    s.parse().ok()
+                }
+            })),
+
+            scene_store: BoolParam::new("Store Scene", false),
+
+            scene_fade_ms: FloatParam::new(
+                "Scene Fade",
+                50.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 2000.0,
+                    factor: 0.4,
+                },
+            )
+            .with_unit(" ms"),
+
+            morph_mode: EnumParam::new("Morph", MorphMode::Off),
+
+            morph_x: FloatParam::new("Morph X", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            morph_y: FloatParam::new("Morph Y", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            random_on: BoolParam::new("Random", false),
+            random_roll: BoolParam::new("Surprise Me", false),
+            random_lock_amp: BoolParam::new("Lock Amp", false),
+            random_lock_cab: BoolParam::new("Lock Cab", false),
+            random_lock_space: BoolParam::new("Lock Space", false),
+            random_seed: Arc::new(RwLock::new(1)),
+
+            scenes: Arc::new(RwLock::new(vec![None; MAX_SCENES])),
+
+            preset: IntParam::new(
+                "Preset",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_PRESETS as i32,
+                },
+            )
+            .with_value_to_string(file_name_formatter(preset_names.clone())),
+            preset_names,
+            preset_save: BoolParam::new("Save Preset", false),
+            preset_load: BoolParam::new("Load Preset", false),
+
             // ----- Simple / macro -----
             input_gain: FloatParam::new(
                 "Input Gain",
@@ -158,6 +691,62 @@ impl Default for GtrParams {
 
             amp_model: EnumParam::new("Amp Model", AmpModel::CrunchTight),
 
+            user_voicing: IntParam::new(
+                "User Voicing",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_USER_VOICINGS as i32,
+                },
+            )
+            .with_value_to_string(file_name_formatter(user_voicing_names.clone())),
+
+            user_voicing_names,
+
+            bass: FloatParam::new("Bass", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            mid: FloatParam::new("Mid", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            treble: FloatParam::new("Treble", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            nam_input_db: FloatParam::new(
+                "Neural Input",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" dB"),
+
+            nam_output_db: FloatParam::new(
+                "Neural Output",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" dB"),
+
+            state_version: Arc::new(RwLock::new(STATE_VERSION)),
+
+            nam_path: Arc::new(RwLock::new(None)),
+
+            nam_model: IntParam::new(
+                "Neural Model",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_NAM_MODELS as i32,
+                },
+            )
+            .with_value_to_string(file_name_formatter(nam_model_names.clone())),
+
+            nam_model_names,
+
+            sag: FloatParam::new("Sag", 0.3, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
             low_cut_hz: FloatParam::new(
                 "Low Cut",
                 110.0, // keeps guitars out of the sub/bass lane
@@ -183,21 +772,228 @@ impl Default for GtrParams {
 
             cab_model: EnumParam::new("Cab", CabModel::TightModern),
 
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 }),
+
+            mix_law: EnumParam::new("Mix Law", MixLaw::EqualPower),
+
+            kill_dry: BoolParam::new("Kill Dry", false),
+
             limiter_on: BoolParam::new("Limiter", true),
+
+            transient_attack: FloatParam::new(
+                "Attack",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_unit(" dB"),
+
+            transient_sustain: FloatParam::new(
+                "Sustain",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_unit(" dB"),
+
+            transient_placement: EnumParam::new("Transient Placement", TransientPlacement::PreAmp),
+
+            // ----- FX -----
+            stutter_on: BoolParam::new("Stutter", false),
+
+            stutter_div: EnumParam::new("Stutter Division", StutterDivision::Sixteenth),
+
+            stutter_repeats: IntParam::new(
+                "Stutter Repeats",
+                4,
+                IntRange::Linear { min: 1, max: 16 },
+            ),
+
+            stutter_pitch: FloatParam::new(
+                "Stutter Pitch",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_unit(" st"),
+
+            stutter_decay: FloatParam::new(
+                "Stutter Decay",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            tapestop_on: BoolParam::new("Tape Stop", false),
+
+            tapestop_mode: EnumParam::new("Tape Stop Mode", TapeStopMode::Stop),
+
+            tapestop_time_ms: FloatParam::new(
+                "Tape Stop Time",
+                600.0,
+                FloatRange::Skewed {
+                    min: 50.0,
+                    max: 4000.0,
+                    factor: 0.4,
+                },
+            )
+            .with_unit(" ms"),
+
+            tapestop_curve: FloatParam::new(
+                "Tape Stop Curve",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            tapestop_spinup_ms: FloatParam::new(
+                "Tape Spin-Up",
+                200.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 2000.0,
+                    factor: 0.4,
+                },
+            )
+            .with_unit(" ms"),
+
+            crush_mix: FloatParam::new(
+                "Crush Mix",
+                0.0, // off until dialed in
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            crush_bits: FloatParam::new(
+                "Crush Bits",
+                8.0,
+                FloatRange::Linear {
+                    min: 1.0,
+                    max: 16.0,
+                },
+            ),
+
+            crush_rate_hz: FloatParam::new(
+                "Crush Rate",
+                11025.0,
+                FloatRange::Skewed {
+                    min: 400.0,
+                    max: 44100.0,
+                    factor: 0.3,
+                },
+            )
+            .with_unit(" Hz"),
+
+            crush_jitter: FloatParam::new(
+                "Crush Jitter",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            crush_filter: EnumParam::new("Crush Filter", CrushFilter::Post),
+
+            crush_placement: EnumParam::new("Crush Placement", CrushPlacement::PostCab),
+
+            double_amount: FloatParam::new(
+                "Doubler",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            double_time_ms: FloatParam::new(
+                "Doubler Time",
+                12.0,
+                FloatRange::Linear {
+                    min: 5.0,
+                    max: 30.0,
+                },
+            )
+            .with_unit(" ms"),
+
+            double_drift: FloatParam::new(
+                "Doubler Drift",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            space_mode: EnumParam::new("Space Mode", SpaceMode::Echo),
+
+            shimmer_amount: FloatParam::new(
+                "Shimmer Amount",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            shimmer_decay: FloatParam::new(
+                "Shimmer Decay",
+                0.6,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            shimmer_interval: EnumParam::new("Shimmer Interval", ShimmerInterval::Octave),
+
+            shimmer_highcut_hz: FloatParam::new(
+                "Shimmer High Cut",
+                6000.0,
+                FloatRange::Skewed {
+                    min: 1000.0,
+                    max: 16000.0,
+                    factor: 0.4,
+                },
+            )
+            .with_unit(" Hz"),
+
+            freeze_on: BoolParam::new("Freeze", false),
+
+            freeze_level: FloatParam::new(
+                "Freeze Level",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            ),
+
+            freeze_fade_ms: FloatParam::new(
+                "Freeze Fade",
+                300.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 4000.0,
+                    factor: 0.4,
+                },
+            )
+            .with_unit(" ms"),
+
+            chain_slot_1: EnumParam::new("Chain Slot 1", ChainModule::Gate),
+            chain_slot_2: EnumParam::new("Chain Slot 2", ChainModule::LowCut),
+            chain_slot_3: EnumParam::new("Chain Slot 3", ChainModule::Amp),
+            chain_slot_4: EnumParam::new("Chain Slot 4", ChainModule::Cab),
+
+            fx_slot_1: EnumParam::new("FX Slot 1", FxModule::Stutter),
+            fx_slot_2: EnumParam::new("FX Slot 2", FxModule::TapeStop),
+            fx_slot_3: EnumParam::new("FX Slot 3", FxModule::Doubler),
+            fx_slot_4: EnumParam::new("FX Slot 4", FxModule::Freeze),
+            fx_slot_5: EnumParam::new("FX Slot 5", FxModule::Space),
+            fx_slot_6: EnumParam::new("FX Slot 6", FxModule::Width),
         }
     }
 }
 
-impl GtrParams {
-    #[inline]
-    pub fn input_linear(&self) -> f32 {
-        db_to_gain(self.input_gain.value())
-    }
-
-    #[inline]
-    pub fn output_linear(&self) -> f32 {
-        db_to_gain(self.output_gain.value())
-    }
+/// Names of the files a file-picking param selects from, in param order.
+pub type FileNames = Arc<RwLock<Vec<String>>>;
+
+/// Display for a 1-based file-picking param: the name of the file, or just
+/// the number while there is no file for it.
+fn file_name_formatter(names: FileNames) -> Arc<dyn Fn(i32) -> String + Send + Sync> {
+    Arc::new(move |v| {
+        let index = (v - 1).max(0) as usize;
+        names
+            .try_read()
+            .ok()
+            .and_then(|names| names.get(index).cloned())
+            .unwrap_or_else(|| v.to_string())
+    })
 }
 
 /// Convert dB value to linear gain. Use this in your DSP code.
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+/// Overrides the per-user data folder.
+const USER_DIR_ENV: &str = "EDNITAR_USER_DIR";
+
+/// Per-user data folder (voicings, NAM models, presets), if one can be found:
+/// `$EDNITAR_USER_DIR`, else the platform's usual app data location. On
+/// Android that's the host app's private files folder.
+pub fn user_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(USER_DIR_ENV) {
+        return Some(PathBuf::from(dir));
+    }
+
+    let base = if cfg!(target_os = "android") {
+        android_files_dir()
+    } else if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+    };
+    base.map(|b| b.join("Ednitar"))
+}
+
+/// `/data/data/<package>/files` of the app hosting us. Apps have no HOME, but
+/// their process name is the package name (plus `:service` for extra
+/// processes), and that folder is always writable without permissions.
+fn android_files_dir() -> Option<PathBuf> {
+    let cmdline = std::fs::read("/proc/self/cmdline").ok()?;
+    let name = cmdline.split(|b| *b == 0).next()?;
+    let name = std::str::from_utf8(name).ok()?;
+    let package = name.split(':').next()?.trim();
+    if package.is_empty() || package.contains('/') {
+        return None;
+    }
+    Some(PathBuf::from("/data/data").join(package).join("files"))
+}
+
+/// Folder scanned for extra amp voicing files.
+pub fn voicings_dir() -> Option<PathBuf> {
+    user_dir().map(|d| d.join("voicings"))
+}
+
+/// Folder scanned for `.nam` neural amp models.
+pub fn nam_models_dir() -> Option<PathBuf> {
+    user_dir().map(|d| d.join("nam"))
+}
+
+/// Folder user presets are saved to and loaded from.
+pub fn presets_dir() -> Option<PathBuf> {
+    user_dir().map(|d| d.join("presets"))
+}
@@ -0,0 +1,112 @@
+//! "Surprise me": seeded randomization of the sound-shaping params.
+//!
+//! Every value is drawn from a range that stays musical, in a fixed order,
+//! so a seed always gives the same sound and locking a group doesn't change
+//! what the other groups get. Triggers, chain order, mix, the NAM/user amp
+//! models and the output stage are never randomized; output gain is capped
+//! at 0 dB and the limiter is forced on.
+
+use crate::dsp::rng::XorShift32;
+use crate::params::{AmpModel, CabModel, ShimmerInterval, SpaceMode};
+use crate::values::ParamValues;
+
+/// Param groups that can be locked against randomizing.
+#[derive(Clone, Copy, Default)]
+pub struct Locks {
+    pub amp: bool,
+    pub cab: bool,
+    pub space: bool,
+}
+
+/// Seed to use after `seed`, so rolling is reproducible too.
+pub fn next_seed(seed: u32) -> u32 {
+    XorShift32::new(seed).next_u32()
+}
+
+/// Uniform in `min..max`.
+fn range(rng: &mut XorShift32, min: f32, max: f32) -> f32 {
+    min + (max - min) * rng.next_f32()
+}
+
+fn pick<T: Copy>(rng: &mut XorShift32, choices: &[T]) -> T {
+    choices[(rng.next_u32() as usize) % choices.len()]
+}
+
+/// `base` with every unlocked group replaced by values drawn from `seed`.
+pub fn randomize(base: &ParamValues, seed: u32, locks: Locks) -> ParamValues {
+    let mut rng = XorShift32::new(seed);
+    let mut v = *base;
+
+    // Amp
+    let amp_model = pick(
+        &mut rng,
+        &[
+            AmpModel::CleanGlass,
+            AmpModel::CrunchTight,
+            AmpModel::LeadEdm,
+        ],
+    );
+    let drive = range(&mut rng, 0.1, 0.9);
+    let bass = range(&mut rng, 0.25, 0.75);
+    let mid = range(&mut rng, 0.2, 0.8);
+    let treble = range(&mut rng, 0.25, 0.75);
+    let sag = range(&mut rng, 0.0, 0.7);
+    let low_cut_hz = range(&mut rng, 60.0, 150.0);
+    if !locks.amp {
+        v.amp_model = amp_model;
+        v.model_morph.play_amp(amp_model);
+        v.drive = drive;
+        v.bass = bass;
+        v.mid = mid;
+        v.treble = treble;
+        v.sag = sag;
+        v.low_cut_hz = low_cut_hz;
+    }
+
+    // Cab (DI bypass isn't a cab sound)
+    let cab_model = pick(
+        &mut rng,
+        &[
+            CabModel::TightModern,
+            CabModel::WarmVintage,
+            CabModel::BrightPop,
+        ],
+    );
+    let tone = range(&mut rng, 0.3, 0.7);
+    let presence = range(&mut rng, 0.0, 0.6);
+    let air = range(&mut rng, 0.0, 0.5);
+    if !locks.cab {
+        v.cab_model = cab_model;
+        v.model_morph.play_cab(cab_model);
+        v.tone = tone;
+        v.presence = presence;
+        v.air = air;
+    }
+
+    // Space
+    let space_mode = pick(&mut rng, &[SpaceMode::Echo, SpaceMode::Shimmer]);
+    let space = range(&mut rng, 0.05, 0.5);
+    let width = range(&mut rng, 0.3, 0.8);
+    let double_amount = range(&mut rng, 0.0, 0.5);
+    let double_time_ms = range(&mut rng, 8.0, 25.0);
+    let shimmer_amount = range(&mut rng, 0.2, 0.7);
+    let shimmer_decay = range(&mut rng, 0.3, 0.8);
+    let shimmer_interval = pick(&mut rng, &[ShimmerInterval::Octave, ShimmerInterval::Fifth]);
+    let shimmer_highcut_hz = range(&mut rng, 3000.0, 9000.0);
+    if !locks.space {
+        v.space_mode = space_mode;
+        v.space = space;
+        v.width = width;
+        v.double_amount = double_amount;
+        v.double_time_ms = double_time_ms;
+        v.shimmer_amount = shimmer_amount;
+        v.shimmer_decay = shimmer_decay;
+        v.shimmer_interval = shimmer_interval;
+        v.shimmer_highcut_hz = shimmer_highcut_hz;
+    }
+
+    // Never louder, never unprotected
+    v.output_gain = v.output_gain.min(0.0);
+    v.limiter_on = true;
+    v
+}
@@ -0,0 +1,270 @@
+//! Scenes: complete param snapshots inside one instance, picked by the
+//! automatable Scene param.
+//!
+//! Scene 0 plays the live params, as does any scene nothing was stored in.
+//! Store Scene captures the live params into the selected scene. Switching
+//! glides all continuous values over the scene fade time on a single engine,
+//! so delay/reverb tails carry over into the next scene. Stepped values
+//! (modes, placements, toggles) switch halfway through, inside a short dip
+//! of the output like a slot order change, so they don't click.
+//!
+//! With Morph on, scenes 1-4 become the corners of an A/B or XY morph and
+//! the Scene param is ignored. Random then varies whatever that gives.
+
+use crate::dsp::slots::SWAP_FADE_S;
+use crate::params::{GtrParams, MorphMode};
+use crate::randomizer::{self, Locks};
+use crate::values::ParamValues;
+
+pub const MAX_SCENES: usize = 8;
+
+pub struct Scenes {
+    /// Audio thread copy of the stored scenes.
+    slots: [Option<ParamValues>; MAX_SCENES],
+    active: usize,
+    morph_mode: MorphMode,
+    store_held: bool,
+    random_on: bool,
+    roll_held: bool,
+    seed: u32,
+    /// New seed waiting to be saved into the plugin state.
+    rolled: Option<u32>,
+    from: ParamValues,
+    to: ParamValues,
+    current: ParamValues,
+    fade: f32,
+    step: f32,
+    /// Values whose stepped params are playing, see `ParamValues::stepped_eq`.
+    stepped: ParamValues,
+    /// Output gain, dipped while the stepped params switch.
+    dip: f32,
+    dip_step: f32,
+}
+
+impl Scenes {
+    pub fn new() -> Self {
+        let v = ParamValues::default();
+        Self {
+            slots: [None; MAX_SCENES],
+            active: 0,
+            morph_mode: MorphMode::Off,
+            store_held: false,
+            random_on: false,
+            roll_held: false,
+            seed: 1,
+            rolled: None,
+            from: v,
+            to: v,
+            current: v,
+            fade: 1.0,
+            step: 1.0,
+            stepped: v,
+            dip: 1.0,
+            dip_step: 1.0,
+        }
+    }
+
+    /// Picks up the scenes saved in the plugin state. Not for the audio thread.
+    pub fn load(&mut self, p: &GtrParams) {
+        if let Ok(saved) = p.scenes.read() {
+            for (slot, snapshot) in self.slots.iter_mut().zip(saved.iter()) {
+                *slot = snapshot.as_ref().map(|s| ParamValues::from_snapshot(p, s));
+            }
+        }
+        self.active = p.scene.value().clamp(0, MAX_SCENES as i32) as usize;
+        self.morph_mode = p.morph_mode.value();
+        self.random_on = p.random_on.value();
+        if let Ok(seed) = p.random_seed.read() {
+            self.seed = *seed;
+        }
+        self.to = self.target(p, &ParamValues::from_params(p));
+        self.current = self.to;
+        self.fade = 1.0;
+        self.stepped = self.to;
+        self.dip = 1.0;
+    }
+
+    /// Called once per block. Handles scene changes and Store Scene; returns
+    /// the scene to persist when one was just stored.
+    pub fn update(&mut self, sr: f32, p: &GtrParams) -> Option<(usize, ParamValues)> {
+        let live = ParamValues::from_params(p);
+
+        let mut stored = None;
+        let store = p.scene_store.value();
+        if store && !self.store_held && self.active > 0 {
+            self.slots[self.active - 1] = Some(live);
+            stored = Some((self.active - 1, live));
+        }
+        self.store_held = store;
+
+        let roll = p.random_roll.value();
+        let rolled = roll && !self.roll_held;
+        if rolled {
+            self.seed = randomizer::next_seed(self.seed);
+            self.rolled = Some(self.seed);
+        }
+        self.roll_held = roll;
+
+        let scene = p.scene.value().clamp(0, MAX_SCENES as i32) as usize;
+        let morph_mode = p.morph_mode.value();
+        let random_on = p.random_on.value();
+        if scene != self.active
+            || morph_mode != self.morph_mode
+            || random_on != self.random_on
+            || (rolled && random_on)
+        {
+            self.active = scene;
+            self.morph_mode = morph_mode;
+            self.random_on = random_on;
+            self.start_fade(sr, p);
+        }
+
+        self.dip_step = 1.0 / (SWAP_FADE_S * sr).max(1.0);
+        self.to = self.target(p, &live);
+        if !self.is_fading() {
+            self.current = self.to;
+            self.stepped = self.to;
+        }
+        stored
+    }
+
+    /// Puts a preset loaded in the background into scene `index` (0-based),
+    /// gliding to it if that scene is playing. Call before `update`.
+    pub fn load_slot(&mut self, sr: f32, p: &GtrParams, index: usize, values: ParamValues) {
+        let Some(slot) = self.slots.get_mut(index) else {
+            return;
+        };
+        *slot = Some(values);
+        let playing = match self.morph_mode {
+            MorphMode::Off => self.active == index + 1,
+            MorphMode::AB => index < 2,
+            MorphMode::XY => index < 4,
+        };
+        if playing {
+            self.start_fade(sr, p);
+        }
+    }
+
+    /// Glides from wherever we are, even mid-fade, to the next target.
+    fn start_fade(&mut self, sr: f32, p: &GtrParams) {
+        self.from = self.current;
+        self.fade = 0.0;
+        self.step = 1.0 / (p.scene_fade_ms.value() * 0.001 * sr).max(1.0);
+    }
+
+    /// Seed rolled since the last call, to be saved into the plugin state.
+    pub fn take_rolled_seed(&mut self) -> Option<u32> {
+        self.rolled.take()
+    }
+
+    fn target(&self, p: &GtrParams, live: &ParamValues) -> ParamValues {
+        let v = self.selected(p, live);
+        if !self.random_on {
+            return v;
+        }
+        let locks = Locks {
+            amp: p.random_lock_amp.value(),
+            cab: p.random_lock_cab.value(),
+            space: p.random_lock_space.value(),
+        };
+        randomizer::randomize(&v, self.seed, locks)
+    }
+
+    /// The scene or morph the params pick, before randomizing.
+    fn selected(&self, p: &GtrParams, live: &ParamValues) -> ParamValues {
+        let x = p.morph_x.value();
+        let y = p.morph_y.value();
+        let weights = match self.morph_mode {
+            MorphMode::Off => {
+                return match self.active {
+                    0 => *live,
+                    n => self.slots[n - 1].unwrap_or(*live),
+                };
+            }
+            MorphMode::AB => [1.0 - x, x, 0.0, 0.0],
+            MorphMode::XY => [(1.0 - x) * (1.0 - y), x * (1.0 - y), (1.0 - x) * y, x * y],
+        };
+        let corners = [0, 1, 2, 3].map(|i| self.slots[i].as_ref().unwrap_or(live));
+        ParamValues::morph(p, corners, weights)
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade < 1.0 || self.dip < 1.0
+    }
+
+    /// Values for the next sample.
+    #[inline]
+    pub fn next(&mut self) -> &ParamValues {
+        let v = if self.fade < 1.0 {
+            self.fade = (self.fade + self.step).min(1.0);
+            ParamValues::blend(&self.from, &self.to, self.fade)
+        } else {
+            self.to
+        };
+
+        // Stepped changes wait for the dip to reach silence
+        if !v.stepped_eq(&self.stepped) {
+            self.dip -= self.dip_step;
+            if self.dip <= 0.0 {
+                self.dip = 0.0;
+                self.stepped = v;
+            }
+        } else if self.dip < 1.0 {
+            self.dip = (self.dip + self.dip_step).min(1.0);
+        }
+
+        self.current = v.with_stepped(&self.stepped);
+        &self.current
+    }
+
+    /// Gain for the output of the sample `next` returned.
+    #[inline]
+    pub fn gain(&self) -> f32 {
+        self.dip
+    }
+
+    /// Values as of the last `update`/`next`.
+    pub fn current(&self) -> &ParamValues {
+        &self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::SpaceMode;
+
+    #[test]
+    fn stepped_values_switch_at_silence() {
+        let sr = 48000.0;
+        let p = GtrParams::default();
+        let mut scenes = Scenes::new();
+        scenes.load(&p);
+        scenes.update(sr, &p);
+
+        let mut target = *scenes.current();
+        target.space_mode = SpaceMode::Shimmer;
+        target.drive = 1.0;
+        scenes.to = target;
+        scenes.start_fade(sr, &p);
+
+        let mut mode = scenes.current().space_mode;
+        let mut dipped = false;
+        for _ in 0..(sr as usize) {
+            if !scenes.is_fading() {
+                break;
+            }
+            let next = scenes.next().space_mode;
+            if next != mode {
+                assert_eq!(scenes.gain(), 0.0);
+                mode = next;
+            }
+            dipped |= scenes.gain() < 1.0;
+        }
+
+        assert!(dipped);
+        assert!(mode == SpaceMode::Shimmer);
+        assert_eq!(scenes.gain(), 1.0);
+        assert_eq!(scenes.current().drive, 1.0);
+    }
+}
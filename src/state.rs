@@ -0,0 +1,209 @@
+//! Saved state versioning.
+//!
+//! Param IDs are the persistent contract with hosts, but what a value *means*
+//! can change between releases. Every saved state records the state version
+//! it was written with, and `migrate` upgrades older states step by step
+//! before nih-plug applies them to the params.
+//!
+//! When a change breaks old projects (a param range or unit changes, enum
+//! variants are inserted or reordered, an ID is renamed), bump
+//! `STATE_VERSION` and append a `Migration` to `MIGRATIONS` that rewrites
+//! params of the previous version into the new layout. It has to cover both
+//! places params are saved: host state stores enums by variant index and
+//! floats by plain value, user presets store everything as display strings.
+
+use nih_plug::prelude::*;
+use nih_plug::wrapper::state::ParamValue;
+use std::collections::BTreeMap;
+
+/// Version of the state layout this build writes.
+pub const STATE_VERSION: u32 = 1;
+
+/// Key of the persisted version field in `GtrParams`.
+pub const STATE_VERSION_KEY: &str = "state_version";
+
+/// Upgrades saved params by one version.
+#[derive(Clone, Copy)]
+pub struct Migration {
+    /// Rewrites params from a host's saved state.
+    pub state: fn(&mut BTreeMap<String, ParamValue>),
+    /// Rewrites params from a user preset file.
+    pub preset: fn(&mut BTreeMap<String, String>),
+}
+
+/// `MIGRATIONS[n]` upgrades a version `n` state to version `n + 1`.
+pub const MIGRATIONS: [Migration; STATE_VERSION as usize] = [V0_TO_V1];
+
+/// States saved before versioning. The params from back then kept their
+/// ranges and meanings, and new enum variants were only ever appended, so
+/// there is nothing to remap yet.
+const V0_TO_V1: Migration = Migration {
+    state: |_| {},
+    preset: |_| {},
+};
+
+/// Brings a saved state up to `STATE_VERSION`. States from this or a newer
+/// build are loaded as they are; params this build doesn't know are ignored
+/// anyway, and the newer version number is kept.
+pub fn migrate(state: &mut PluginState) {
+    migrate_with(state, &MIGRATIONS);
+}
+
+/// `migrate` up to version `steps.len()`.
+fn migrate_with(state: &mut PluginState, steps: &[Migration]) {
+    let target = steps.len() as u32;
+    let version = state
+        .fields
+        .get(STATE_VERSION_KEY)
+        .and_then(|v| serde_json::from_str::<u32>(v).ok())
+        .unwrap_or(0);
+    if version >= target {
+        return;
+    }
+
+    for step in &steps[version as usize..] {
+        (step.state)(&mut state.params);
+    }
+    nih_log!("Migrated state from version {version} to {target}");
+
+    // whatever gets saved next is in this build's layout
+    if let Ok(v) = serde_json::to_string(&target) {
+        state.fields.insert(STATE_VERSION_KEY.to_string(), v);
+    }
+}
+
+/// Brings the params of a preset saved at `version` up to version
+/// `steps.len()`, and returns the version they're at now.
+pub fn migrate_preset(
+    params: &mut BTreeMap<String, String>,
+    version: u32,
+    steps: &[Migration],
+) -> u32 {
+    let target = steps.len() as u32;
+    if version >= target {
+        return version;
+    }
+    for step in &steps[version as usize..] {
+        (step.preset)(params);
+    }
+    target
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(version: Option<u32>) -> PluginState {
+        let mut fields = BTreeMap::new();
+        if let Some(version) = version {
+            fields.insert(STATE_VERSION_KEY.to_string(), version.to_string());
+        }
+        fields.insert("nam_path".to_string(), "null".to_string());
+        PluginState {
+            version: "0.1.0".to_string(),
+            params: BTreeMap::from([
+                ("edm_drive".to_string(), ParamValue::F32(0.7)),
+                ("edm_amp_model".to_string(), ParamValue::I32(2)),
+                ("edm_bypass".to_string(), ParamValue::Bool(false)),
+            ]),
+            fields,
+        }
+    }
+
+    /// Compared as JSON, the way hosts store them.
+    fn params(state: &PluginState) -> serde_json::Value {
+        serde_json::to_value(&state.params).unwrap()
+    }
+
+    fn version(state: &PluginState) -> Option<u32> {
+        state
+            .fields
+            .get(STATE_VERSION_KEY)
+            .and_then(|v| serde_json::from_str(v).ok())
+    }
+
+    #[test]
+    fn unversioned_state_migrates_to_current() {
+        let mut s = state(None);
+        migrate(&mut s);
+        assert_eq!(version(&s), Some(STATE_VERSION));
+        assert_eq!(params(&s), params(&state(None)));
+    }
+
+    #[test]
+    fn current_state_is_left_alone() {
+        let mut s = state(Some(STATE_VERSION));
+        migrate(&mut s);
+        assert_eq!(params(&s), params(&state(Some(STATE_VERSION))));
+        assert_eq!(s.fields, state(Some(STATE_VERSION)).fields);
+    }
+
+    #[test]
+    fn newer_state_is_not_downgraded() {
+        let mut s = state(Some(STATE_VERSION + 1));
+        migrate(&mut s);
+        assert_eq!(version(&s), Some(STATE_VERSION + 1));
+        assert_eq!(params(&s), params(&state(None)));
+    }
+
+    /// A later version where a new amp model went in at index 1 and Low
+    /// Cut moved from kHz to Hz.
+    const V1_TO_V2: Migration = Migration {
+        state: |params| {
+            // indices can come back as either number type
+            match params.get_mut("edm_amp_model") {
+                Some(ParamValue::I32(i)) if *i >= 1 => *i += 1,
+                Some(ParamValue::F32(i)) if *i >= 1.0 => *i += 1.0,
+                _ => {}
+            }
+            if let Some(ParamValue::F32(hz)) = params.get_mut("edm_low_cut_hz") {
+                *hz *= 1000.0;
+            }
+        },
+        preset: |_| {},
+    };
+
+    /// A numeric param as hosts store it, whichever variant it parsed into.
+    fn number(state: &PluginState, id: &str) -> Option<f64> {
+        params(state).get(id)?.as_f64()
+    }
+
+    #[test]
+    fn migration_remaps_values_and_round_trips() {
+        let steps = [V0_TO_V1, V1_TO_V2];
+        let mut s = state(None);
+        s.params
+            .insert("edm_low_cut_hz".to_string(), ParamValue::F32(0.1));
+        migrate_with(&mut s, &steps);
+        assert_eq!(version(&s), Some(2));
+        assert_eq!(number(&s, "edm_amp_model"), Some(3.0));
+        assert!((number(&s, "edm_low_cut_hz").unwrap() - 100.0).abs() < 1e-3);
+        assert_eq!(number(&s, "edm_drive"), Some(0.7f32 as f64));
+
+        // saved and loaded again, nothing gets remapped twice
+        let saved = serde_json::to_string(&s).unwrap();
+        let mut loaded: PluginState = serde_json::from_str(&saved).unwrap();
+        migrate_with(&mut loaded, &steps);
+        assert_eq!(version(&loaded), Some(2));
+        assert_eq!(number(&loaded, "edm_amp_model"), Some(3.0));
+        assert!((number(&loaded, "edm_low_cut_hz").unwrap() - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn migrated_state_round_trips() {
+        let saved = serde_json::to_string(&state(None)).unwrap();
+        let loaded: PluginState = serde_json::from_str(&saved).unwrap();
+        let mut s: PluginState = serde_json::from_str(&saved).unwrap();
+        migrate(&mut s);
+
+        let resaved = serde_json::to_string(&s).unwrap();
+        let mut reloaded: PluginState = serde_json::from_str(&resaved).unwrap();
+        assert_eq!(params(&reloaded), params(&loaded));
+        assert_eq!(reloaded.fields, s.fields);
+
+        // loading what was saved after the migration changes nothing
+        migrate(&mut reloaded);
+        assert_eq!(params(&reloaded), params(&loaded));
+        assert_eq!(reloaded.fields, s.fields);
+    }
+}
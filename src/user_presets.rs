@@ -0,0 +1,285 @@
+//! User presets as portable, human-readable JSON files.
+//!
+//! A preset stores every param by ID as its display string (`"Crunch Tight"`,
+//! `"-6.0"`, `"On"`), so files can be written and diffed by hand and survive
+//! reordered enums. Metadata records who made it and which build wrote it.
+//!
+//! A plugin can't move its own knobs, so Load Preset puts a preset into the
+//! selected scene, which then plays it like any stored scene.
+//!
+//! Hosts don't list these files: CLAP preset discovery needs the plugin to
+//! load what the host picks (the preset-load extension), and the nih-plug
+//! wrapper supports neither.
+
+use nih_plug::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::state::{self, Migration, MIGRATIONS, STATE_VERSION};
+use crate::values::Snapshot;
+
+/// File extension of user presets.
+pub const PRESET_EXT: &str = "json";
+/// Most presets the Preset param can pick from.
+pub const MAX_PRESETS: usize = 64;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct PresetMeta {
+    pub name: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Plugin version that saved the preset.
+    #[serde(default)]
+    pub plugin_version: String,
+    /// State layout version, see `state::migrate`.
+    #[serde(default)]
+    pub state_version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PresetFile {
+    #[serde(flatten)]
+    pub meta: PresetMeta,
+    /// Param ID -> value as displayed, without unit.
+    pub params: BTreeMap<String, String>,
+}
+
+impl PresetFile {
+    /// Snapshots the current param values.
+    pub fn capture(mut meta: PresetMeta, params: &dyn Params) -> Self {
+        meta.plugin_version = env!("CARGO_PKG_VERSION").to_string();
+        meta.state_version = STATE_VERSION;
+
+        let params = params
+            .param_map()
+            .into_iter()
+            .map(|(id, ptr, _)| {
+                // SAFETY: the pointers come straight from the live params object
+                let value = unsafe {
+                    ptr.normalized_value_to_string(ptr.unmodulated_normalized_value(), false)
+                };
+                (id, value)
+            })
+            .collect();
+
+        Self { meta, params }
+    }
+
+    /// The preset as a scene snapshot, migrated from the state version it
+    /// was saved with. Params missing from the file get their defaults.
+    /// Also returns the IDs in the file that couldn't be applied.
+    pub fn to_snapshot(&self, params: &dyn Params) -> (Snapshot, Vec<String>) {
+        self.to_snapshot_with(params, &MIGRATIONS)
+    }
+
+    /// `to_snapshot` migrating with `steps`, see `state::migrate_preset`.
+    fn to_snapshot_with(
+        &self,
+        params: &dyn Params,
+        steps: &[Migration],
+    ) -> (Snapshot, Vec<String>) {
+        let mut values = self.params.clone();
+        state::migrate_preset(&mut values, self.meta.state_version, steps);
+
+        let map = params.param_map();
+        let mut skipped: Vec<String> = values
+            .keys()
+            .filter(|id| !map.iter().any(|(known, _, _)| known == *id))
+            .cloned()
+            .collect();
+
+        let mut snapshot = Snapshot::new();
+        for (id, ptr, _) in map {
+            // SAFETY: the pointers come straight from the live params object
+            let normalized = unsafe {
+                match values.get(&id) {
+                    Some(value) => match ptr.string_to_normalized_value(value) {
+                        Some(n) => n,
+                        None => {
+                            skipped.push(id);
+                            continue;
+                        }
+                    },
+                    None => ptr.default_normalized_value(),
+                }
+            };
+            snapshot.insert(id, normalized);
+        }
+        (snapshot, skipped)
+    }
+
+    /// Writes the preset into `dir` as `<name>.json`, replacing any preset
+    /// with the same name.
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        let path = dir.join(format!("{}.{PRESET_EXT}", file_stem(&self.meta.name)));
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, text).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+/// Presets in `dir`, sorted by name, at most `MAX_PRESETS`. Unreadable and
+/// surplus files are logged and skipped.
+pub fn list(dir: &Path) -> Vec<(PathBuf, PresetMeta)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut presets: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == PRESET_EXT))
+        .filter_map(|path| match PresetFile::load(&path) {
+            Ok(preset) => Some((path, preset.meta)),
+            Err(e) => {
+                nih_error!("Skipping preset {e}");
+                None
+            }
+        })
+        .collect();
+    presets.sort_by_key(|(_, meta)| meta.name.to_lowercase());
+
+    if presets.len() > MAX_PRESETS {
+        for (path, _) in &presets[MAX_PRESETS..] {
+            nih_log!(
+                "Skipping preset {}: only the first {MAX_PRESETS} can be selected",
+                path.display()
+            );
+        }
+        presets.truncate(MAX_PRESETS);
+    }
+    presets
+}
+
+/// First "Preset <n>" that neither names one of `presets` nor would
+/// overwrite a file in `dir`.
+pub fn unused_name(dir: &Path, presets: &[(PathBuf, PresetMeta)]) -> String {
+    (1..)
+        .map(|n| format!("Preset {n}"))
+        .find(|name| {
+            !presets
+                .iter()
+                .any(|(_, meta)| meta.name.eq_ignore_ascii_case(name))
+                && !dir
+                    .join(format!("{}.{PRESET_EXT}", file_stem(name)))
+                    .exists()
+        })
+        .unwrap_or_default()
+}
+
+/// Preset name made safe for file systems, including Android's.
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if stem.is_empty() {
+        "Untitled".to_string()
+    } else {
+        stem
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::{AmpModel, GtrParams};
+    use crate::values::ParamValues;
+
+    fn display<P: Param>(param: &P, plain: P::Plain) -> String {
+        param.normalized_value_to_string(param.preview_normalized(plain), false)
+    }
+
+    #[test]
+    fn preset_round_trips_into_scene_values() {
+        let params = GtrParams::default();
+        let meta = PresetMeta {
+            name: "Round Trip".to_string(),
+            ..PresetMeta::default()
+        };
+        let mut preset = PresetFile::capture(meta, &params);
+        preset
+            .params
+            .insert("edm_drive".to_string(), display(&params.drive, 0.8));
+        preset.params.insert(
+            "edm_amp_model".to_string(),
+            display(&params.amp_model, AmpModel::LeadEdm),
+        );
+        preset.params.remove("edm_bass");
+
+        let dir = std::env::temp_dir().join(format!("ednitar-presets-{}", std::process::id()));
+        let path = preset.save(&dir).unwrap();
+        let listed = list(&dir);
+        let loaded = PresetFile::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].1.name, "Round Trip");
+        assert_eq!(loaded.meta.state_version, STATE_VERSION);
+
+        let (snapshot, skipped) = loaded.to_snapshot(&params);
+        assert!(skipped.is_empty(), "{skipped:?}");
+        let v = ParamValues::from_snapshot(&params, &snapshot);
+        assert!((v.drive - 0.8).abs() < 1e-3, "{}", v.drive);
+        assert!(v.amp_model == AmpModel::LeadEdm);
+        assert_eq!(v.bass, params.bass.default_plain_value());
+        assert_eq!(v.treble, params.treble.default_plain_value());
+    }
+
+    #[test]
+    fn old_preset_is_migrated_before_loading() {
+        // a later version where the amp model "Lead" was renamed and Low
+        // Cut moved from kHz to Hz
+        const V1_TO_V2: Migration = Migration {
+            state: |_| {},
+            preset: |params| {
+                if params.get("edm_amp_model").is_some_and(|v| v == "Lead") {
+                    let name = AmpModel::variants()[AmpModel::LeadEdm.to_index()];
+                    params.insert("edm_amp_model".to_string(), name.to_string());
+                }
+                if let Some(khz) = params.get_mut("edm_low_cut_hz") {
+                    let hz = khz.parse::<f32>().unwrap_or(0.11) * 1000.0;
+                    *khz = hz.to_string();
+                }
+            },
+        };
+        let steps = [state::MIGRATIONS[0], V1_TO_V2];
+
+        let params = GtrParams::default();
+        let preset: PresetFile = serde_json::from_str(
+            r#"{
+                "name": "Old",
+                "state_version": 1,
+                "params": { "edm_amp_model": "Lead", "edm_low_cut_hz": "0.08" }
+            }"#,
+        )
+        .unwrap();
+
+        let (snapshot, skipped) = preset.to_snapshot_with(&params, &steps);
+        assert!(skipped.is_empty(), "{skipped:?}");
+        let v = ParamValues::from_snapshot(&params, &snapshot);
+        assert!(v.amp_model == AmpModel::LeadEdm);
+        assert!((v.low_cut_hz - 80.0).abs() < 0.5, "{}", v.low_cut_hz);
+
+        // without the step the old name means nothing
+        let (_, skipped) = preset.to_snapshot(&params);
+        assert!(
+            skipped.iter().any(|id| id == "edm_amp_model"),
+            "{skipped:?}"
+        );
+    }
+}
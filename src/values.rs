@@ -0,0 +1,421 @@
+//! Plain values of every sound-shaping param, as the DSP sees them.
+//!
+//! The DSP doesn't read `GtrParams` directly: each block the plugin builds a
+//! `ParamValues` from the live params, or from a stored snapshot, or a blend
+//! of two, applies CLAP param modulation on top, and hands that down. Host
+//! bypass and the controls that pick snapshots stay outside of it.
+
+use nih_plug::prelude::*;
+use std::collections::BTreeMap;
+
+use crate::dsp::slots::resolve_order;
+use crate::params::*;
+
+/// Stored param values by param ID, normalized so they survive range
+/// changes the same way host automation does.
+pub type Snapshot = BTreeMap<String, f32>;
+
+/// Blending between two values. Continuous values glide, stepped ones
+/// switch halfway.
+pub trait Blend: Copy + PartialEq {
+    /// Whether the value jumps instead of gliding.
+    const STEPPED: bool = true;
+
+    fn blend(a: Self, b: Self, t: f32) -> Self;
+}
+
+impl Blend for f32 {
+    const STEPPED: bool = false;
+
+    #[inline]
+    fn blend(a: Self, b: Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+}
+
+impl Blend for i32 {
+    #[inline]
+    fn blend(a: Self, b: Self, t: f32) -> Self {
+        (a as f32 + (b - a) as f32 * t).round() as i32
+    }
+}
+
+macro_rules! blend_stepped {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl Blend for $t {
+                #[inline]
+                fn blend(a: Self, b: Self, t: f32) -> Self {
+                    if t < 0.5 {
+                        a
+                    } else {
+                        b
+                    }
+                }
+            }
+        )*
+    };
+}
+
+blend_stepped!(
+    bool,
+    AmpModel,
+    CabModel,
+    MixLaw,
+    ChainModule,
+    FxModule,
+    TransientPlacement,
+    StutterDivision,
+    TapeStopMode,
+    CrushFilter,
+    CrushPlacement,
+    SpaceMode,
+    ShimmerInterval,
+);
+
+/// Interpolates one param across the four morph corners with `weights`
+/// (summing to 1). Continuous params blend in normalized space so skewed
+/// ranges sweep the way their knobs do; stepped ones take the heaviest corner.
+fn morph_param<P: Param>(param: &P, values: [P::Plain; 4], weights: [f32; 4]) -> P::Plain
+where
+    P::Plain: Copy,
+{
+    if param.step_count().is_none() {
+        let normalized: f32 = values
+            .iter()
+            .zip(weights)
+            .map(|(v, w)| param.preview_normalized(*v) * w)
+            .sum();
+        param.preview_plain(normalized)
+    } else {
+        values[heaviest(weights)]
+    }
+}
+
+fn heaviest(weights: [f32; 4]) -> usize {
+    (1..4).fold(
+        0,
+        |best, i| if weights[i] > weights[best] { i } else { best },
+    )
+}
+
+/// Most amp or cab models heard at once: one per morph corner.
+pub const MORPH_MODELS: usize = 4;
+
+/// Each model among `values` with its total weight across the corners,
+/// credited to its first corner. Repeats get no weight of their own.
+fn shares<T: Copy + PartialEq>(values: [T; 4], weights: [f32; 4]) -> [(T, f32); MORPH_MODELS] {
+    let mut shares = values.map(|v| (v, 0.0));
+    for (v, w) in values.iter().zip(weights) {
+        let first = values.iter().position(|u| u == v).unwrap_or(0);
+        shares[first].1 += w;
+    }
+    shares
+}
+
+/// `a` crossfading over to `b`, `t` of the way.
+fn crossfade<T: Copy + PartialEq>(a: T, b: T, t: f32) -> [(T, f32); MORPH_MODELS] {
+    if a == b {
+        [(a, 1.0), (a, 0.0), (a, 0.0), (a, 0.0)]
+    } else {
+        [(a, 1.0 - t), (b, t), (b, 0.0), (b, 0.0)]
+    }
+}
+
+/// Amp and cab models run in parallel while morphing between snapshots that
+/// use different ones, each with its share of the output. A model is listed
+/// at most once with a share above zero.
+#[derive(Clone, Copy)]
+pub struct ModelMorph {
+    pub amps: [(AmpModel, f32); MORPH_MODELS],
+    pub cabs: [(CabModel, f32); MORPH_MODELS],
+}
+
+impl ModelMorph {
+    fn none(amp_model: AmpModel, cab_model: CabModel) -> Self {
+        Self {
+            amps: crossfade(amp_model, amp_model, 0.0),
+            cabs: crossfade(cab_model, cab_model, 0.0),
+        }
+    }
+
+    /// Hear only `model` from the amp.
+    pub fn play_amp(&mut self, model: AmpModel) {
+        self.amps = crossfade(model, model, 0.0);
+    }
+
+    /// Hear only `model` from the cab.
+    pub fn play_cab(&mut self, model: CabModel) {
+        self.cabs = crossfade(model, model, 0.0);
+    }
+
+    #[inline]
+    pub fn amp_share(&self, model: AmpModel) -> f32 {
+        share(&self.amps, model)
+    }
+
+    #[inline]
+    pub fn cab_share(&self, model: CabModel) -> f32 {
+        share(&self.cabs, model)
+    }
+}
+
+#[inline]
+fn share<T: PartialEq>(shares: &[(T, f32); MORPH_MODELS], model: T) -> f32 {
+    shares
+        .iter()
+        .find(|(m, _)| *m == model)
+        .map_or(0.0, |&(_, w)| w)
+}
+
+/// Stepped params that switch without a dip: the amp and cab models
+/// crossfade in parallel, the slot orders fade in `SlotOrder`.
+fn fades_itself(id: &str) -> bool {
+    matches!(id, "edm_amp_model" | "edm_cab_model")
+        || id.starts_with("edm_chain_slot")
+        || id.starts_with("edm_fx_slot")
+}
+
+macro_rules! param_values {
+    ($($field:ident: $ty:ty = $id:literal,)*) => {
+        #[derive(Clone, Copy)]
+        pub struct ParamValues {
+            $(pub $field: $ty,)*
+            /// Parallel amp/cab crossfade, not a param of its own.
+            pub model_morph: ModelMorph,
+        }
+
+        /// Number of params in `ParamValues`.
+        pub const PARAM_COUNT: usize = [$($id),*].len();
+
+        /// Host modulation per param, in normalized units.
+        pub type ModOffsets = [f32; PARAM_COUNT];
+
+        impl ParamValues {
+            /// Current values of the live params, without host modulation.
+            pub fn from_params(p: &GtrParams) -> Self {
+                Self {
+                    $($field: p.$field.unmodulated_plain_value(),)*
+                    model_morph: ModelMorph::none(
+                        p.amp_model.unmodulated_plain_value(),
+                        p.cab_model.unmodulated_plain_value(),
+                    ),
+                }
+            }
+
+            /// Values stored in `snapshot`. Params it doesn't have (e.g. ones
+            /// added after it was taken) keep their live value.
+            pub fn from_snapshot(p: &GtrParams, snapshot: &Snapshot) -> Self {
+                Self {
+                    $(
+                        $field: match snapshot.get($id) {
+                            Some(n) => p.$field.preview_plain(*n),
+                            None => p.$field.unmodulated_plain_value(),
+                        },
+                    )*
+                    model_morph: ModelMorph::none(
+                        p.amp_model.unmodulated_plain_value(),
+                        p.cab_model.unmodulated_plain_value(),
+                    ),
+                }
+                .with_own_models()
+            }
+
+            /// How far CLAP param modulation currently moves each param.
+            pub fn modulation_offsets(p: &GtrParams) -> ModOffsets {
+                [$(
+                    p.$field.modulated_normalized_value() - p.$field.unmodulated_normalized_value(),
+                )*]
+            }
+
+            /// Applies host modulation on top of these values, the way the
+            /// host applies it on top of the param: as an offset on the knob.
+            /// A modulated amp or cab model replaces whatever was morphing.
+            pub fn with_modulation(mut self, p: &GtrParams, offsets: &ModOffsets) -> Self {
+                let (amp_model, cab_model) = (self.amp_model, self.cab_model);
+                let mut offsets = offsets.iter().copied();
+                $(
+                    let offset = offsets.next().unwrap_or(0.0);
+                    if offset != 0.0 {
+                        let normalized = p.$field.preview_normalized(self.$field) + offset;
+                        self.$field = p.$field.preview_plain(normalized.clamp(0.0, 1.0));
+                    }
+                )*
+                if self.amp_model != amp_model {
+                    self.model_morph.play_amp(self.amp_model);
+                }
+                if self.cab_model != cab_model {
+                    self.model_morph.play_cab(self.cab_model);
+                }
+                self
+            }
+
+            /// Stores these values by param ID.
+            pub fn to_snapshot(self, p: &GtrParams) -> Snapshot {
+                let mut snapshot = Snapshot::new();
+                $(snapshot.insert($id.to_string(), p.$field.preview_normalized(self.$field));)*
+                snapshot
+            }
+
+            /// Whether the stepped params match, apart from the ones that
+            /// fade on their own (see `fades_itself`).
+            #[inline]
+            pub fn stepped_eq(&self, other: &Self) -> bool {
+                $((!<$ty as Blend>::STEPPED || fades_itself($id) || self.$field == other.$field))&&*
+            }
+
+            /// These values with the stepped params of `other`, see `stepped_eq`.
+            #[inline]
+            pub fn with_stepped(mut self, other: &Self) -> Self {
+                $(
+                    if <$ty as Blend>::STEPPED && !fades_itself($id) {
+                        self.$field = other.$field;
+                    }
+                )*
+                self
+            }
+
+            /// Straight glide from `a` to `b`. Amp and cab models crossfade in
+            /// parallel instead of switching halfway.
+            #[inline]
+            pub fn blend(a: &Self, b: &Self, t: f32) -> Self {
+                Self {
+                    $($field: Blend::blend(a.$field, b.$field, t),)*
+                    model_morph: ModelMorph {
+                        amps: crossfade(a.amp_model, b.amp_model, t),
+                        cabs: crossfade(a.cab_model, b.cab_model, t),
+                    },
+                }
+            }
+
+            /// Weighted morph across four snapshots, see `morph_param`.
+            pub fn morph(p: &GtrParams, corners: [&Self; 4], weights: [f32; 4]) -> Self {
+                Self {
+                    $($field: morph_param(&p.$field, corners.map(|c| c.$field), weights),)*
+                    model_morph: ModelMorph {
+                        amps: shares(corners.map(|c| c.amp_model), weights),
+                        cabs: shares(corners.map(|c| c.cab_model), weights),
+                    },
+                }
+            }
+        }
+    };
+}
+
+param_values! {
+    input_gain: f32 = "edm_in_gain",
+    drive: f32 = "edm_drive",
+    tone: f32 = "edm_tone",
+    space: f32 = "edm_space",
+    width: f32 = "edm_width",
+    output_gain: f32 = "edm_out_gain",
+    gate_threshold: f32 = "edm_gate_thresh",
+    gate_release_ms: f32 = "edm_gate_release",
+    amp_model: AmpModel = "edm_amp_model",
+    user_voicing: i32 = "edm_user_voicing",
+    bass: f32 = "edm_bass",
+    mid: f32 = "edm_mid",
+    treble: f32 = "edm_treble",
+    nam_input_db: f32 = "edm_nam_in",
+    nam_output_db: f32 = "edm_nam_out",
+    sag: f32 = "edm_sag",
+    low_cut_hz: f32 = "edm_low_cut_hz",
+    presence: f32 = "edm_presence",
+    air: f32 = "edm_air",
+    cab_model: CabModel = "edm_cab_model",
+    mix: f32 = "edm_mix",
+    mix_law: MixLaw = "edm_mix_law",
+    kill_dry: bool = "edm_kill_dry",
+    limiter_on: bool = "edm_limiter_on",
+    transient_attack: f32 = "edm_trans_attack",
+    transient_sustain: f32 = "edm_trans_sustain",
+    transient_placement: TransientPlacement = "edm_trans_place",
+    stutter_on: bool = "edm_stutter_on",
+    stutter_div: StutterDivision = "edm_stutter_div",
+    stutter_repeats: i32 = "edm_stutter_repeats",
+    stutter_pitch: f32 = "edm_stutter_pitch",
+    stutter_decay: f32 = "edm_stutter_decay",
+    tapestop_on: bool = "edm_tapestop_on",
+    tapestop_mode: TapeStopMode = "edm_tapestop_mode",
+    tapestop_time_ms: f32 = "edm_tapestop_time",
+    tapestop_curve: f32 = "edm_tapestop_curve",
+    tapestop_spinup_ms: f32 = "edm_tapestop_spinup",
+    crush_mix: f32 = "edm_crush_mix",
+    crush_bits: f32 = "edm_crush_bits",
+    crush_rate_hz: f32 = "edm_crush_rate",
+    crush_jitter: f32 = "edm_crush_jitter",
+    crush_filter: CrushFilter = "edm_crush_filter",
+    crush_placement: CrushPlacement = "edm_crush_place",
+    double_amount: f32 = "edm_double_amount",
+    double_time_ms: f32 = "edm_double_time",
+    double_drift: f32 = "edm_double_drift",
+    space_mode: SpaceMode = "edm_space_mode",
+    shimmer_amount: f32 = "edm_shimmer_amount",
+    shimmer_decay: f32 = "edm_shimmer_decay",
+    shimmer_interval: ShimmerInterval = "edm_shimmer_interval",
+    shimmer_highcut_hz: f32 = "edm_shimmer_highcut",
+    freeze_on: bool = "edm_freeze_on",
+    freeze_level: f32 = "edm_freeze_level",
+    freeze_fade_ms: f32 = "edm_freeze_fade",
+    chain_slot_1: ChainModule = "edm_chain_slot1",
+    chain_slot_2: ChainModule = "edm_chain_slot2",
+    chain_slot_3: ChainModule = "edm_chain_slot3",
+    chain_slot_4: ChainModule = "edm_chain_slot4",
+    fx_slot_1: FxModule = "edm_fx_slot1",
+    fx_slot_2: FxModule = "edm_fx_slot2",
+    fx_slot_3: FxModule = "edm_fx_slot3",
+    fx_slot_4: FxModule = "edm_fx_slot4",
+    fx_slot_5: FxModule = "edm_fx_slot5",
+    fx_slot_6: FxModule = "edm_fx_slot6",
+}
+
+impl Default for ParamValues {
+    fn default() -> Self {
+        Self::from_params(&GtrParams::default())
+    }
+}
+
+impl ParamValues {
+    fn with_own_models(mut self) -> Self {
+        self.model_morph = ModelMorph::none(self.amp_model, self.cab_model);
+        self
+    }
+
+    #[inline]
+    pub fn input_linear(&self) -> f32 {
+        db_to_gain(self.input_gain)
+    }
+
+    #[inline]
+    pub fn output_linear(&self) -> f32 {
+        db_to_gain(self.output_gain)
+    }
+
+    /// Guitar chain processing order from the slot params.
+    pub fn chain_order(&self) -> [ChainModule; CHAIN_SLOTS] {
+        resolve_order(
+            [
+                self.chain_slot_1,
+                self.chain_slot_2,
+                self.chain_slot_3,
+                self.chain_slot_4,
+            ],
+            DEFAULT_CHAIN_ORDER,
+        )
+    }
+
+    /// Stereo FX processing order from the slot params.
+    pub fn fx_order(&self) -> [FxModule; FX_SLOTS] {
+        resolve_order(
+            [
+                self.fx_slot_1,
+                self.fx_slot_2,
+                self.fx_slot_3,
+                self.fx_slot_4,
+                self.fx_slot_5,
+                self.fx_slot_6,
+            ],
+            DEFAULT_FX_ORDER,
+        )
+    }
+}